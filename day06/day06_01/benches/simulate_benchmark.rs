@@ -0,0 +1,30 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use day06_01::{parse_timers, simulate, simulate_naive, Population};
+
+const INPUT_FN: &str = "input";
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let contents = std::fs::read_to_string(INPUT_FN).unwrap();
+    let timers: Vec<u8> = parse_timers(&contents)
+        .unwrap()
+        .into_iter()
+        .map(|timer| timer as u8)
+        .collect();
+    let population: Population = contents.parse().unwrap();
+
+    c.bench_function("naive 80", |b| {
+        b.iter(|| simulate_naive(black_box(&timers), 80))
+    });
+    c.bench_function("buckets 80", |b| {
+        b.iter(|| simulate(black_box(&population), 80))
+    });
+    c.bench_function("buckets 256", |b| {
+        b.iter(|| simulate(black_box(&population), 256))
+    });
+    c.bench_function("matrix 256", |b| {
+        b.iter(|| black_box(&population).count_after(256))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
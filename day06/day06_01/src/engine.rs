@@ -0,0 +1,90 @@
+//! Running simulations, with progress reporting and a brute force reference
+//!
+//! The simulation runs on a single thread. A day only moves the counts of a handful of
+//! buckets, nine for lanternfish, and every day depends on the one before,
+//! so there is no work that could be split across threads without costing more than it saves.
+//! Many days are counted in logarithmic time with `Population::count_after` instead.
+
+use crate::{FishSpecies, Population};
+
+/// The total number of fish after `days` days, starting with `initial`
+///
+/// # Panics
+/// If the population no longer fits into a `u128`, use `Population::count_after` for that many days
+pub fn simulate(initial: &Population, days: usize) -> u128 {
+    simulate_with_progress(initial, days, |_, _| {})
+}
+
+/// The total number of fish after `days` days, starting with `initial`.
+/// `on_day` is called with the day and the population after each day passed
+///
+/// # Panics
+/// If the population no longer fits into a `u128`, use `Population::count_after` for that many days
+pub fn simulate_with_progress<F: FnMut(usize, &Population)>(
+    initial: &Population,
+    days: usize,
+    mut on_day: F,
+) -> u128 {
+    let mut population = initial.clone();
+    for day in 1..=days {
+        population.step();
+        on_day(day, &population);
+    }
+    population.count()
+}
+
+/// The total number of lanternfish after `days` days, starting with a fish for each of `timers`,
+/// simulating every fish on it's own. Takes time and memory linear in the result
+pub fn simulate_naive(timers: &[u8], days: usize) -> u128 {
+    let species = FishSpecies::lanternfish();
+    let mut fishes = timers.to_vec();
+    for _ in 0..days {
+        let mut born = 0;
+        for fish in fishes.iter_mut() {
+            if *fish == 0 {
+                born += 1;
+                *fish = species.reset_timer as u8;
+            } else {
+                *fish -= 1;
+            }
+        }
+        fishes.resize(fishes.len() + born, species.newborn_timer as u8);
+    }
+    fishes.len() as u128
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_simulate() {
+        let population: Population = "3,4,3,1,2".parse().unwrap();
+        assert_eq!(5, simulate(&population, 0));
+        assert_eq!(26, simulate(&population, 18));
+        assert_eq!(26984457539, simulate(&population, 256));
+        for days in [0, 1, 18, 80] {
+            assert_eq!(
+                simulate_naive(&[3, 4, 3, 1, 2], days),
+                simulate(&population, days)
+            );
+        }
+
+        let mut progress = Vec::new();
+        let count = simulate_with_progress(&population, 18, |day, population| {
+            progress.push((day, population.count()))
+        });
+        assert_eq!(26, count);
+        assert_eq!(18, progress.len());
+        assert_eq!((1, 5), progress[0]);
+        assert_eq!((18, 26), progress[17]);
+    }
+
+    #[test]
+    fn test_simulate_empty() {
+        let population = Population::from_timers(&[]).unwrap();
+        assert_eq!(0, simulate(&population, 256));
+        assert_eq!(0, simulate_naive(&[], 256));
+        assert_eq!(1, simulate_naive(&[3], 3));
+    }
+}
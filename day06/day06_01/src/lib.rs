@@ -0,0 +1,212 @@
+//! Counting a growing population of lanternfish
+//!
+//! Every fish has a timer counting down the days until it gives birth.
+//! Since fish with the same timer behave the same, a population is stored
+//! as the number of fish for each timer value instead of one entry per fish.
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+
+use num_bigint::BigUint;
+use species::Transition;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+mod engine;
+mod matrix;
+mod series;
+mod species;
+
+pub use engine::{simulate, simulate_naive, simulate_with_progress};
+pub use series::TimeSeries;
+pub use species::FishSpecies;
+
+/// The timer of a fish right after it gave birth
+pub const RESET_TIMER: usize = 6;
+
+/// The timer of a newborn fish
+pub const NEWBORN_TIMER: usize = 8;
+
+/// Errors that can occur while parsing a population
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A timer is not a number
+    InvalidNumber(String, ParseIntError),
+
+    /// A timer is larger than any timer of the species
+    TimerOutOfRange(usize),
+}
+
+/// Parse a comma separated list of timers, like `3,4,3,1,2`
+pub fn parse_timers(s: &str) -> Result<Vec<usize>, ParseError> {
+    s.trim()
+        .split(',')
+        .map(|timer| {
+            let timer = timer.trim();
+            timer
+                .parse()
+                .map_err(|e| ParseError::InvalidNumber(timer.to_string(), e))
+        })
+        .collect()
+}
+
+/// The number of fish of a species in each state, a state being a timer value
+/// and, if the species has a maximum lifespan, an age
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Population {
+    /// How the fish give birth, age and die
+    species: FishSpecies,
+
+    /// The number of fish in each state, indexed by `FishSpecies::state`
+    buckets: Vec<u128>,
+
+    /// The moves between states in a single day, cached from the species
+    transitions: Vec<Transition>,
+}
+
+impl Population {
+    /// Construct a population of `species` with one newborn fish for each of `timers`
+    pub fn new(species: FishSpecies, timers: &[usize]) -> Result<Self, ParseError> {
+        let mut buckets = vec![0; species.states()];
+        for &timer in timers {
+            if timer >= species.timers() {
+                return Err(ParseError::TimerOutOfRange(timer));
+            }
+            buckets[species.state(timer, 0)] += 1;
+        }
+        Ok(Self {
+            species,
+            buckets,
+            transitions: species.transitions(),
+        })
+    }
+
+    /// Construct a population of lanternfish with one fish for each of `timers`
+    pub fn from_timers(timers: &[usize]) -> Result<Self, ParseError> {
+        Self::new(FishSpecies::lanternfish(), timers)
+    }
+
+    /// The species of the fish
+    pub fn species(&self) -> &FishSpecies {
+        &self.species
+    }
+
+    /// The number of fish for each timer value, indexed by timer
+    pub fn buckets(&self) -> Vec<u128> {
+        let mut ret = vec![0; self.species.timers()];
+        for (state, count) in self.buckets.iter().enumerate() {
+            ret[self.species.timer(state)] += count;
+        }
+        ret
+    }
+
+    /// The total number of fish
+    pub fn count(&self) -> u128 {
+        self.buckets.iter().sum()
+    }
+
+    /// Let a single day pass
+    ///
+    /// # Panics
+    /// If the population no longer fits into a `u128`, use `count_after` for that many days
+    pub fn step(&mut self) {
+        let mut next = vec![0u128; self.buckets.len()];
+        for transition in &self.transitions {
+            next[transition.to] = self.buckets[transition.from]
+                .checked_mul(transition.factor)
+                .and_then(|count| count.checked_add(next[transition.to]))
+                .expect("population overflows a u128");
+        }
+        self.buckets = next;
+    }
+
+    /// Let `days` days pass
+    ///
+    /// # Panics
+    /// If the population no longer fits into a `u128`, use `count_after` for that many days
+    pub fn advance(&mut self, days: usize) {
+        for _ in 0..days {
+            self.step();
+        }
+    }
+
+    /// The total number of fish after `days` days, for any number of days.
+    /// Takes `O(log days)` multiplications of a square matrix with a row per state
+    pub fn count_after(&self, days: u64) -> BigUint {
+        matrix::count_after(&self.transitions, &self.buckets, days)
+    }
+}
+
+impl FromStr for Population {
+    type Err = ParseError;
+
+    /// Parses a comma separated list of lanternfish timers, like `3,4,3,1,2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_timers(&parse_timers(s)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The population of the example
+    fn example() -> Population {
+        "3,4,3,1,2".parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(vec![0, 1, 1, 2, 1, 0, 0, 0, 0], example().buckets());
+        assert_eq!(Ok(example()), " 3, 4,3,1,2\n".parse());
+        assert!(matches!(
+            "3,x".parse::<Population>(),
+            Err(ParseError::InvalidNumber(s, _)) if s == "x"
+        ));
+        assert!(matches!(
+            "3,,4".parse::<Population>(),
+            Err(ParseError::InvalidNumber(_, _))
+        ));
+        assert_eq!(
+            Err(ParseError::TimerOutOfRange(9)),
+            "3,9".parse::<Population>()
+        );
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut population = example();
+        population.advance(18);
+        assert_eq!(26, population.count());
+        population.advance(80 - 18);
+        assert_eq!(5934, population.count());
+        population.advance(256 - 80);
+        assert_eq!(26984457539, population.count());
+    }
+
+    #[test]
+    fn test_count_after() {
+        let population = example();
+        assert_eq!(BigUint::from(5u8), population.count_after(0));
+        assert_eq!(BigUint::from(26u8), population.count_after(18));
+        assert_eq!(BigUint::from(5934u16), population.count_after(80));
+        assert_eq!(BigUint::from(26984457539u64), population.count_after(256));
+
+        let mut stepped = example();
+        for days in 0..=600 {
+            assert_eq!(BigUint::from(stepped.count()), population.count_after(days));
+            stepped.step();
+        }
+    }
+
+    #[test]
+    fn test_count_after_many_days() {
+        // The transition matrix `M` satisfies `M^9 = M^2 + 1`,
+        // so the counts satisfy `f(d) = f(d - 7) + f(d - 9)`
+        let population = example();
+        let days = 100_000;
+        assert_eq!(
+            population.count_after(days),
+            population.count_after(days - 7) + population.count_after(days - 9)
+        );
+    }
+}
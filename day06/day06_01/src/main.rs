@@ -1,53 +1,33 @@
+use day06_01::{simulate, Population};
+
+/// The usage of the command line arguments
+const USAGE: &str = "usage: day06_01 [csv DAYS]";
+
 fn main() {
-    //let start = [3, 4, 3, 1, 2];
-    let start: [u8; 300] = [
-        1, 4, 1, 1, 1, 1, 1, 1, 1, 4, 3, 1, 1, 3, 5, 1, 5, 3, 2, 1, 1, 2, 3, 1, 1, 5, 3, 1, 5, 1,
-        1, 2, 1, 2, 1, 1, 3, 1, 5, 1, 1, 1, 3, 1, 1, 1, 1, 1, 1, 4, 5, 3, 1, 1, 1, 1, 1, 1, 2, 1,
-        1, 1, 1, 4, 4, 4, 1, 1, 1, 1, 5, 1, 2, 4, 1, 1, 4, 1, 2, 1, 1, 1, 2, 1, 5, 1, 1, 1, 3, 4,
-        1, 1, 1, 3, 2, 1, 1, 1, 4, 1, 1, 1, 5, 1, 1, 4, 1, 1, 2, 1, 4, 1, 1, 1, 3, 1, 1, 1, 1, 1,
-        3, 1, 3, 1, 1, 2, 1, 4, 1, 1, 1, 1, 3, 1, 1, 1, 1, 1, 1, 2, 1, 3, 1, 1, 1, 1, 4, 1, 1, 1,
-        1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 1, 1, 5, 1, 1, 1, 2, 2, 1, 1, 3, 5, 1, 1, 1, 1, 3, 1, 3, 3,
-        1, 1, 1, 1, 3, 5, 2, 1, 1, 1, 1, 5, 1, 1, 1, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 2, 1, 1, 1, 1,
-        1, 2, 1, 1, 1, 1, 1, 5, 1, 4, 3, 3, 1, 3, 4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 3, 5, 1, 1,
-        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 5, 2, 1, 4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 5, 1,
-        1, 1, 1, 1, 1, 1, 1, 2, 1, 4, 4, 1, 1, 1, 1, 1, 1, 1, 5, 1, 1, 2, 5, 1, 1, 4, 1, 3, 1, 1,
-    ];
-    println!("{}", start.len());
-    let mut chunks = start.chunks(300 / 12);
-    println!("{}", chunks.len());
+    let contents = std::fs::read_to_string("input").unwrap();
+    let population: Population = contents.parse().unwrap();
 
-    let mut threads = Vec::new();
-    for (ii, mut chunk) in chunks.enumerate() {
-        let mut fishes = chunk.to_vec();
-        let t = std::thread::spawn(move || {
-            let chunks = fishes.chunks(fishes.len() / 25);
-            let mut count_local = 0_u128;
-            let mut new_fishes = Vec::new();
-            for (jj, chunk) in chunks.enumerate() {
-                let mut fishes = chunk.to_vec();
-                for day in 0..256 {
-                    println!("thread {:5} day {:5} chunk {:5} fishes {:20}", ii, day, jj, fishes.len());
-                    for fish in fishes.iter_mut() {
-                        if *fish == 0 {
-                            new_fishes.push(8);
-                            *fish = 6;
-                        } else {
-                            *fish = *fish - 1;
-                        }
-                    }
-                    fishes.extend_from_slice(&mut new_fishes);
-                    new_fishes = Vec::new();
-                }
-                count_local += fishes.len() as u128;
-            }
-            count_local
-        });
-        threads.push(t);
+    // `csv DAYS` prints the population of each day instead
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match &args[..] {
+        [] => {}
+        [cmd, days] if cmd == "csv" => {
+            let days = days.parse().unwrap_or_else(|e| {
+                eprintln!("invalid number of days {}: {}\n{}", days, e, USAGE);
+                std::process::exit(1)
+            });
+            population
+                .time_series(days)
+                .write_csv(std::io::stdout().lock())
+                .unwrap();
+            return;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1)
+        }
     }
-    let mut all = 0_u128;
-    for tt in threads {
-        let count = tt.join().unwrap();
-        all += count;
-    }
-    println!("{}", all);
+
+    println!("{}", simulate(&population, 80));
+    println!("{}", simulate(&population, 256));
 }
@@ -0,0 +1,66 @@
+//! Counting fish after any number of days by exponentiating the daily transition matrix
+
+use crate::Transition;
+use num_bigint::BigUint;
+
+/// A square matrix with a row and a column per state
+type Matrix = Vec<Vec<BigUint>>;
+
+/// The identity matrix of size `n`
+fn identity(n: usize) -> Matrix {
+    (0..n)
+        .map(|row| {
+            (0..n)
+                .map(|col| BigUint::from((row == col) as u8))
+                .collect()
+        })
+        .collect()
+}
+
+/// The matrix of size `n` taking the buckets of one day to the buckets of the next day.
+/// Entry `(i, j)` is the number of fish in state `i` a fish in state `j` turns into
+fn transition(transitions: &[Transition], n: usize) -> Matrix {
+    let mut ret = vec![vec![BigUint::default(); n]; n];
+    for transition in transitions {
+        ret[transition.to][transition.from] += transition.factor;
+    }
+    ret
+}
+
+/// The product `a * b`
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let n = a.len();
+    (0..n)
+        .map(|row| {
+            (0..n)
+                .map(|col| (0..n).map(|k| &a[row][k] * &b[k][col]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+/// The transition matrix raised to the power of `days`, by repeated squaring
+fn power(transitions: &[Transition], n: usize, mut days: u64) -> Matrix {
+    let mut ret = identity(n);
+    let mut base = transition(transitions, n);
+    while days > 0 {
+        if days & 1 == 1 {
+            ret = mul(&ret, &base);
+        }
+        days >>= 1;
+        if days > 0 {
+            base = mul(&base, &base);
+        }
+    }
+    ret
+}
+
+/// The total number of fish after `days` days, starting with `buckets`
+pub(crate) fn count_after(transitions: &[Transition], buckets: &[u128], days: u64) -> BigUint {
+    let matrix = power(transitions, buckets.len(), days);
+    matrix
+        .iter()
+        .flat_map(|row| row.iter().zip(buckets))
+        .map(|(entry, &count)| entry * count)
+        .sum()
+}
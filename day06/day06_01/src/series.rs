@@ -0,0 +1,96 @@
+//! Recording the size of a population day by day
+
+use crate::Population;
+use std::io::{self, Write};
+
+/// The number of fish for each timer value on each day of a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeSeries {
+    /// The number of fish for each timer value, one entry per day starting with day `0`
+    days: Vec<Vec<u128>>,
+}
+
+impl TimeSeries {
+    /// The number of fish for each timer value on `day`, `None` if it was not simulated
+    pub fn day(&self, day: usize) -> Option<&[u128]> {
+        self.days.get(day).map(Vec::as_slice)
+    }
+
+    /// The total number of fish on each day
+    pub fn totals(&self) -> Vec<u128> {
+        self.days
+            .iter()
+            .map(|buckets| buckets.iter().sum())
+            .collect()
+    }
+
+    /// Write this series as CSV, with a header row followed by one row per day
+    /// holding the day, the total number of fish and the number of fish for each timer value
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let timers = self.days.first().map_or(0, Vec::len);
+        write!(w, "day,total")?;
+        for timer in 0..timers {
+            write!(w, ",timer_{}", timer)?;
+        }
+        writeln!(w)?;
+        for (day, buckets) in self.days.iter().enumerate() {
+            write!(w, "{},{}", day, buckets.iter().sum::<u128>())?;
+            for count in buckets {
+                write!(w, ",{}", count)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    /// Render this series as CSV, like `write_csv`
+    pub fn to_csv(&self) -> String {
+        let mut ret = Vec::new();
+        self.write_csv(&mut ret)
+            .expect("writing to a Vec never fails");
+        String::from_utf8(ret).expect("CSV is always valid UTF-8")
+    }
+}
+
+impl Population {
+    /// Record this population for each of the next `days` days, including today
+    ///
+    /// # Panics
+    /// If the population no longer fits into a `u128`
+    pub fn time_series(&self, days: usize) -> TimeSeries {
+        let mut population = self.clone();
+        let mut ret = vec![population.buckets()];
+        for _ in 0..days {
+            population.step();
+            ret.push(population.buckets());
+        }
+        TimeSeries { days: ret }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_series() {
+        let population: Population = "3,4,3,1,2".parse().unwrap();
+        let series = population.time_series(18);
+        assert_eq!(
+            vec![5, 5, 6, 7, 9, 10, 10, 10, 10, 11, 12, 15, 17, 19, 20, 20, 21, 22, 26],
+            series.totals()
+        );
+        assert_eq!(Some(&[1, 1, 2, 1, 0, 0, 0, 0, 0][..]), series.day(1));
+        assert_eq!(None, series.day(19));
+
+        let csv = population.time_series(2).to_csv();
+        assert_eq!(
+            "day,total,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8
+0,5,0,1,1,2,1,0,0,0,0
+1,5,1,1,2,1,0,0,0,0,0
+2,6,1,2,1,0,0,0,1,0,1
+",
+            csv
+        );
+    }
+}
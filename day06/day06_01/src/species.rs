@@ -0,0 +1,173 @@
+//! The lifecycle parameters of a species of fish
+
+use crate::{NEWBORN_TIMER, RESET_TIMER};
+use std::num::NonZeroUsize;
+
+/// How fish of a species give birth, age and die
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FishSpecies {
+    /// The timer of a fish right after it gave birth,
+    /// so it gives birth every `reset_timer + 1` days
+    pub reset_timer: usize,
+
+    /// The timer of a newborn fish,
+    /// so it first gives birth after `newborn_timer + 1` days
+    pub newborn_timer: usize,
+
+    /// The number of days a fish lives, `None` if fish never die.
+    /// The fish of the initial population are counted as born on day `0`,
+    /// and a fish due to give birth on it's last day still does so
+    pub max_lifespan: Option<NonZeroUsize>,
+
+    /// The number of fish born each time a fish gives birth
+    pub litter_size: u128,
+}
+
+impl Default for FishSpecies {
+    /// Lanternfish
+    fn default() -> Self {
+        Self::lanternfish()
+    }
+}
+
+/// A share of the fish in one state moving to another state in a single day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Transition {
+    /// The state the fish leave
+    pub(crate) from: usize,
+
+    /// The state the fish enter
+    pub(crate) to: usize,
+
+    /// The number of fish entering `to` for every fish leaving `from`
+    pub(crate) factor: u128,
+}
+
+impl FishSpecies {
+    /// Lanternfish, giving birth to a single fish every `7` days and living forever
+    pub fn lanternfish() -> Self {
+        Self {
+            reset_timer: RESET_TIMER,
+            newborn_timer: NEWBORN_TIMER,
+            max_lifespan: None,
+            litter_size: 1,
+        }
+    }
+
+    /// The number of distinct timer values
+    pub(crate) fn timers(&self) -> usize {
+        self.reset_timer.max(self.newborn_timer) + 1
+    }
+
+    /// The number of distinct ages that have to be told apart
+    fn ages(&self) -> usize {
+        self.max_lifespan.map_or(1, NonZeroUsize::get)
+    }
+
+    /// The number of distinct states a fish can be in
+    pub(crate) fn states(&self) -> usize {
+        self.timers() * self.ages()
+    }
+
+    /// The state of a fish with timer `timer` and age `age`
+    pub(crate) fn state(&self, timer: usize, age: usize) -> usize {
+        age * self.timers() + timer
+    }
+
+    /// The timer of a fish in state `state`
+    pub(crate) fn timer(&self, state: usize) -> usize {
+        state % self.timers()
+    }
+
+    /// All ways fish move between states in a single day
+    pub(crate) fn transitions(&self) -> Vec<Transition> {
+        let mut ret = Vec::new();
+        for age in 0..self.ages() {
+            // Without a lifespan, all fish are kept at age `0`
+            let next_age = match self.max_lifespan {
+                Some(max) if age + 1 >= max.get() => None,
+                Some(_) => Some(age + 1),
+                None => Some(age),
+            };
+            for timer in 0..self.timers() {
+                let from = self.state(timer, age);
+                if timer == 0 {
+                    ret.push(Transition {
+                        from,
+                        to: self.state(self.newborn_timer, 0),
+                        factor: self.litter_size,
+                    });
+                }
+                if let Some(next_age) = next_age {
+                    let next_timer = if timer == 0 {
+                        self.reset_timer
+                    } else {
+                        timer - 1
+                    };
+                    ret.push(Transition {
+                        from,
+                        to: self.state(next_timer, next_age),
+                        factor: 1,
+                    });
+                }
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ParseError, Population};
+    use num_bigint::BigUint;
+
+    /// A single fish of `species` about to give birth
+    fn single(species: FishSpecies) -> Population {
+        Population::new(species, &[0]).unwrap()
+    }
+
+    #[test]
+    fn test_litter_size() {
+        let species = FishSpecies {
+            litter_size: 2,
+            ..FishSpecies::lanternfish()
+        };
+        let series = single(species).time_series(8);
+        assert_eq!(vec![1, 3, 3, 3, 3, 3, 3, 3, 5], series.totals());
+    }
+
+    #[test]
+    fn test_max_lifespan() {
+        let species = FishSpecies {
+            max_lifespan: NonZeroUsize::new(3),
+            ..FishSpecies::lanternfish()
+        };
+        let series = single(species).time_series(5);
+        assert_eq!(vec![1, 2, 2, 1, 0, 0], series.totals());
+    }
+
+    #[test]
+    fn test_custom_species() {
+        let species = FishSpecies {
+            reset_timer: 2,
+            newborn_timer: 4,
+            max_lifespan: NonZeroUsize::new(10),
+            litter_size: 3,
+        };
+        assert_eq!(
+            Err(ParseError::TimerOutOfRange(5)),
+            Population::new(species, &[1, 5])
+        );
+
+        let population = Population::new(species, &[1, 4]).unwrap();
+        assert_eq!(5, population.buckets().len());
+        let mut stepped = population.clone();
+        for days in 0..60 {
+            if days % 7 == 0 {
+                assert_eq!(BigUint::from(stepped.count()), population.count_after(days));
+            }
+            stepped.step();
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! The fuel crabs burn moving to a position
+
+use crate::fuel_cost;
+use std::ops::RangeInclusive;
+
+/// The fuel a crab burns to move a given distance
+pub trait CostModel {
+    /// The fuel burned moving `distance` steps
+    fn cost(&self, distance: u32) -> u64;
+
+    /// The positions an optimal alignment is searched in, given the sorted non-empty `positions`.
+    /// Defaults to every position between the outermost crabs
+    fn candidates(&self, positions: &[i32]) -> RangeInclusive<i32> {
+        positions[0]..=positions[positions.len() - 1]
+    }
+}
+
+/// Every step burns a single unit of fuel, so the optimum is at the median
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Linear;
+
+impl CostModel for Linear {
+    fn cost(&self, distance: u32) -> u64 {
+        distance.into()
+    }
+
+    fn candidates(&self, positions: &[i32]) -> RangeInclusive<i32> {
+        let median = positions[(positions.len() - 1) / 2];
+        median..=median
+    }
+}
+
+/// Every step burns one unit of fuel more than the one before,
+/// so the optimum is next to the mean
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Triangular;
+
+impl CostModel for Triangular {
+    fn cost(&self, distance: u32) -> u64 {
+        fuel_cost(distance)
+    }
+
+    fn candidates(&self, positions: &[i32]) -> RangeInclusive<i32> {
+        let sum: i64 = positions.iter().map(|&pos| i64::from(pos)).sum();
+        let len = positions.len() as i64;
+        // The optimum is within half a step of the mean
+        let low = sum.div_euclid(len);
+        let high = low + 1;
+        let (min, max) = (positions[0], positions[positions.len() - 1]);
+        low.max(min.into()) as i32..=high.min(max.into()) as i32
+    }
+}
+
+impl<F: Fn(u32) -> u64> CostModel for F {
+    fn cost(&self, distance: u32) -> u64 {
+        self(distance)
+    }
+}
@@ -1,19 +1,239 @@
-fn fuel_cost(xx: i32) -> i32 {
-    return ((xx * xx) / 2) + (xx / 2);
-}
-
-pub fn solve(numbers: &[i32]) -> u32 {
-    let mut dist_low = u32::MAX;
-    for xx in 0..=500 {
-        let mut dist_now = 0_u32;
-        for yy in numbers {
-            let dist = (xx - yy).abs();
-            let dist = fuel_cost(dist);
-            dist_now += dist as u32;
+//! Aligning crabs at the position burning the least fuel
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+
+mod cost;
+
+pub use cost::{CostModel, Linear, Triangular};
+use std::num::ParseIntError;
+
+/// The fuel burned moving `distance` steps, each step burning one more than the one before.
+/// This is the triangular number `n(n+1)/2`, which never overflows a `u64` for a `u32` distance
+fn fuel_cost(distance: u32) -> u64 {
+    let distance = u64::from(distance);
+    distance * (distance + 1) / 2
+}
+
+/// Errors that can occur while parsing the positions of crabs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// There are no positions at all
+    Empty,
+
+    /// A position is not a number
+    InvalidNumber(String, ParseIntError),
+}
+
+/// Parse a comma separated list of positions like `16,1,2`, of any length.
+/// Whitespace around the positions is ignored
+pub fn parse_positions(s: &str) -> Result<Vec<i32>, ParseError> {
+    if s.trim().is_empty() {
+        return Err(ParseError::Empty);
+    }
+    s.split(',')
+        .map(|pos| {
+            let pos = pos.trim();
+            pos.parse()
+                .map_err(|e| ParseError::InvalidNumber(pos.to_string(), e))
+        })
+        .collect()
+}
+
+/// Errors that can occur while aligning crabs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignError {
+    /// There are no crabs to align
+    NoCrabs,
+
+    /// The total fuel burned overflows a `u64` at every candidate position
+    Overflow,
+}
+
+/// The position all crabs move to, and the fuel they burn on the way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    /// The position all crabs move to
+    pub position: i32,
+
+    /// The total fuel burned
+    pub cost: u64,
+}
+
+/// The total fuel burned moving the crabs at `positions` to `target`,
+/// `None` if it overflows a `u64`
+fn total_cost<C: CostModel>(positions: &[i32], target: i32, model: &C) -> Option<u64> {
+    positions.iter().try_fold(0u64, |acc, &pos| {
+        acc.checked_add(model.cost(pos.abs_diff(target)))
+    })
+}
+
+/// The alignment of the crabs at `positions` burning the least fuel under `model`,
+/// preferring the lowest position on ties.
+/// Takes `O(n log n)` for models narrowing down the candidates,
+/// and `O(range * n)` for those searching every position between the outermost crabs
+pub fn align<C: CostModel>(positions: &[i32], model: &C) -> Result<Alignment, AlignError> {
+    let mut sorted = positions.to_vec();
+    sorted.sort_unstable();
+    if sorted.is_empty() {
+        return Err(AlignError::NoCrabs);
+    }
+    // A position whose total overflows can not be the optimum unless all others overflow too
+    model
+        .candidates(&sorted)
+        .filter_map(|position| {
+            total_cost(&sorted, position, model).map(|cost| Alignment { position, cost })
+        })
+        .min_by_key(|alignment| alignment.cost)
+        .ok_or(AlignError::Overflow)
+}
+
+/// The least fuel burned aligning the crabs at `numbers` under the `Triangular` cost model
+pub fn solve(numbers: &[i32]) -> Result<u64, AlignError> {
+    align(numbers, &Triangular).map(|alignment| alignment.cost)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The positions of the example
+    const EXAMPLE: [i32; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+    /// The triangular cost summing every step on it's own
+    fn naive_cost(distance: u32) -> u64 {
+        (1..=u64::from(distance)).sum()
+    }
+
+    /// The alignment of `positions` found by trying every position between the outermost crabs
+    fn brute_force<C: CostModel>(positions: &[i32], model: &C) -> Alignment {
+        let min = *positions.iter().min().unwrap();
+        let max = *positions.iter().max().unwrap();
+        (min..=max)
+            .map(|position| Alignment {
+                position,
+                cost: total_cost(positions, position, model).unwrap(),
+            })
+            .min_by_key(|alignment| alignment.cost)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_positions() {
+        assert_eq!(
+            Ok(EXAMPLE.to_vec()),
+            parse_positions("16,1,2,0,4,2,7,1,2,14\n")
+        );
+        assert_eq!(Ok(vec![-3, 4]), parse_positions(" -3, 4 "));
+        assert_eq!(Err(ParseError::Empty), parse_positions(" \n"));
+        assert!(matches!(
+            parse_positions("1,x,3"),
+            Err(ParseError::InvalidNumber(s, _)) if s == "x"
+        ));
+        assert!(matches!(
+            parse_positions("1,,3"),
+            Err(ParseError::InvalidNumber(s, _)) if s.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_linear() {
+        assert_eq!(
+            Ok(Alignment {
+                position: 2,
+                cost: 37
+            }),
+            align(&EXAMPLE, &Linear)
+        );
+        assert_eq!(Err(AlignError::NoCrabs), align(&[], &Linear));
+        assert_eq!(
+            Ok(Alignment {
+                position: -3,
+                cost: 0
+            }),
+            align(&[-3], &Linear)
+        );
+    }
+
+    #[test]
+    fn test_triangular() {
+        assert_eq!(
+            Ok(Alignment {
+                position: 5,
+                cost: 168
+            }),
+            align(&EXAMPLE, &Triangular)
+        );
+        assert_eq!(Ok(168), solve(&EXAMPLE));
+        for distance in 0..2000 {
+            assert_eq!(naive_cost(distance), fuel_cost(distance));
+        }
+        assert_eq!(naive_cost(100_000), Triangular.cost(100_000));
+    }
+
+    #[test]
+    fn test_custom() {
+        // A closure searches every position between the outermost crabs
+        let square = |distance: u32| u64::from(distance) * u64::from(distance);
+        assert_eq!(
+            brute_force(&EXAMPLE, &square),
+            align(&EXAMPLE, &square).unwrap()
+        );
+        assert_eq!(5, align(&EXAMPLE, &square).unwrap().position);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let input = std::fs::read_to_string("input").unwrap();
+        let positions = parse_positions(&input).unwrap();
+        for positions in [&EXAMPLE[..], &positions[..], &[5, -5, 100, 7, 7, 8]] {
+            assert_eq!(
+                brute_force(positions, &Linear).cost,
+                align(positions, &Linear).unwrap().cost
+            );
+            assert_eq!(
+                brute_force(positions, &Triangular),
+                align(positions, &Triangular).unwrap()
+            );
         }
-        if dist_low > dist_now {
-            dist_low = dist_now;
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        // Sum every step once up front, brute force would take too long otherwise
+        let naive: Vec<u64> = (0..1000).map(naive_cost).collect();
+        // A linear congruential generator is random enough for reproducible inputs
+        let mut seed = 7u32;
+        let mut range = |low: i32, high: i32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            low + (seed >> 16) as i32 % (high - low)
+        };
+        for _ in 0..500 {
+            let len = range(1, 40) as usize;
+            let spread = range(1, 500);
+            let positions: Vec<i32> = (0..len).map(|_| range(-spread, spread)).collect();
+            assert_eq!(
+                brute_force(&positions, &|distance: u32| naive[distance as usize]),
+                align(&positions, &Triangular).unwrap()
+            );
+            assert_eq!(
+                brute_force(&positions, &|distance: u32| u64::from(distance)).cost,
+                align(&positions, &Linear).unwrap().cost
+            );
         }
     }
-    dist_low
+
+    #[test]
+    fn test_overflow() {
+        // Each crab burns about `2^61` fuel moving to the middle
+        let mut positions = vec![i32::MIN; 10];
+        positions.extend([i32::MAX; 10]);
+        assert_eq!(Err(AlignError::Overflow), align(&positions, &Triangular));
+        assert_eq!(
+            Ok(Alignment {
+                position: i32::MIN,
+                cost: 20 * (1 << 31) - 10
+            }),
+            align(&positions, &Linear)
+        );
+    }
 }
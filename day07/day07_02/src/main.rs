@@ -1,21 +1,24 @@
-use day07_02::solve;
-use std::io::Read;
+use day07_02::{parse_positions, solve};
+
+/// The input read if no path is given as argument
+const DEFAULT_INPUT: &str = "../input";
 
 fn main() {
-    let mut numbers = [0i32; 1000];
-    let mut fd = std::fs::File::open("../input").unwrap();
-    let mut contents = String::new();
-    fd.read_to_string(&mut contents).unwrap();
-    let mut split = contents
-        .split(',')
-        .filter(|s| *s != "")
-        .map(|s| s.replace('\n', ""));
-    for ii in 0..numbers.len() {
-        let s = split.next().unwrap();
-        numbers[ii] = s.parse().unwrap();
-    }
+    let path = std::env::args().nth(1);
+    let path = path.as_deref().unwrap_or(DEFAULT_INPUT);
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("can not read {}: {}", path, e);
+        std::process::exit(1)
+    });
+    let numbers = parse_positions(&contents).unwrap_or_else(|e| {
+        eprintln!("invalid positions in {}: {:?}", path, e);
+        std::process::exit(1)
+    });
 
-    let dist_low = solve(&numbers);
+    let dist_low = solve(&numbers).unwrap_or_else(|e| {
+        eprintln!("can not align crabs: {:?}", e);
+        std::process::exit(1)
+    });
 
     println!("{}", dist_low);
 }
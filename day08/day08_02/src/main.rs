@@ -8,6 +8,13 @@ fn main() {
     let parsed = parse(&contents);
     let ans = part_one(&parsed);
     println!("part one {}", ans);
+    match part_two(&parsed) {
+        Ok(ans) => println!("part two {}", ans),
+        Err(e) => {
+            eprintln!("can not decode: {:?}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 const COUNT_ONE: usize = 2;
@@ -25,7 +32,54 @@ fn part_one(parsed: &[(Vec<&str>, Vec<&str>)]) -> usize {
         .count()
 }
 
-#[derive(Default, Debug)]
+/// The sum of the numbers shown by the outputs of every display
+fn part_two(parsed: &[(Vec<&str>, Vec<&str>)]) -> Result<u64, DecodeError> {
+    let mut sum = 0;
+    for (signals, outputs) in parsed {
+        let display = SevenSegmentDisplay::deduce(signals)?;
+        let mut number = 0;
+        for output in outputs {
+            number = number * 10 + u64::from(display.decode(output)?);
+        }
+        sum += number;
+    }
+    Ok(sum)
+}
+
+/// Errors that can occur while deducing the wiring of a display
+#[derive(Debug, PartialEq, Eq)]
+enum DecodeError {
+    /// A display does not have exactly ten signal patterns
+    PatternCount(usize),
+
+    /// A pattern uses a wire other than `a` to `g`
+    InvalidWire(char),
+
+    /// The signal patterns do not fit any wiring of the segments
+    Inconsistent,
+
+    /// An output pattern does not show any digit
+    UnknownPattern(String),
+}
+
+/// The wires of a display
+const WIRES: &str = "abcdefg";
+
+/// The number of distinct digits, each shown by one of the signal patterns
+const COUNT_DIGITS: usize = 10;
+
+/// The number of digits lighting up `top_left`
+const FREQ_TOP_LEFT: usize = 6;
+/// The number of digits lighting up `bottom_left`
+const FREQ_BOTTOM_LEFT: usize = 4;
+/// The number of digits lighting up `bottom_right`
+const FREQ_BOTTOM_RIGHT: usize = 9;
+/// The number of digits lighting up `top` or `top_right`, the latter being part of `1`
+const FREQ_TOP_OR_TOP_RIGHT: usize = 8;
+/// The number of digits lighting up `middle` or `bottom`, the former being part of `4`
+const FREQ_MIDDLE_OR_BOTTOM: usize = 7;
+
+#[derive(Default, Debug, PartialEq, Eq)]
 struct SevenSegmentDisplay {
     top: char,
     top_left: char,
@@ -136,31 +190,91 @@ impl SevenSegmentDisplay {
             && output.contains(self.bottom)
             && output.contains(self.middle)
     }
-    fn decode(&self, output: &str) -> u8 {
+    fn decode(&self, output: &str) -> Result<u8, DecodeError> {
         if self.is_zero(output) {
-            0
+            Ok(0)
         } else if self.is_one(output) {
-            1
+            Ok(1)
         } else if self.is_two(output) {
-            2
+            Ok(2)
         } else if self.is_three(output) {
-            3
+            Ok(3)
         } else if self.is_four(output) {
-            4
+            Ok(4)
         } else if self.is_five(output) {
-            5
+            Ok(5)
         } else if self.is_six(output) {
-            6
+            Ok(6)
         } else if self.is_seven(output) {
-            7
+            Ok(7)
         } else if self.is_eight(output) {
-            8
+            Ok(8)
         } else if self.is_nine(output) {
-            9
+            Ok(9)
         } else {
-            panic!("unknown pattern {}", output);
+            Err(DecodeError::UnknownPattern(output.to_owned()))
         }
     }
+
+    /// Deduce the wiring from the ten signal patterns of a display, in any order.
+    /// Every segment is lit by a distinct number of digits, except for two pairs
+    /// told apart by the patterns of `1` and `4`
+    fn deduce(signals: &[&str]) -> Result<Self, DecodeError> {
+        if signals.len() != COUNT_DIGITS {
+            return Err(DecodeError::PatternCount(signals.len()));
+        }
+        let mut freqs = [0; WIRES.len()];
+        for signal in signals {
+            for wire in signal.chars() {
+                let idx = WIRES.find(wire).ok_or(DecodeError::InvalidWire(wire))?;
+                freqs[idx] += 1;
+            }
+        }
+        let uniq: UniqSignals = signals.to_vec().into();
+        if uniq.one.is_empty() || uniq.four.is_empty() {
+            return Err(DecodeError::Inconsistent);
+        }
+
+        // Indexed from `top` to `bottom`, in the order of the fields
+        let mut segments: [Option<char>; 7] = [None; 7];
+        for (wire, freq) in WIRES.chars().zip(freqs) {
+            let segment = match freq {
+                FREQ_TOP_OR_TOP_RIGHT if uniq.one.contains(wire) => 2,
+                FREQ_TOP_OR_TOP_RIGHT => 0,
+                FREQ_TOP_LEFT => 1,
+                FREQ_MIDDLE_OR_BOTTOM if uniq.four.contains(wire) => 3,
+                FREQ_MIDDLE_OR_BOTTOM => 6,
+                FREQ_BOTTOM_LEFT => 4,
+                FREQ_BOTTOM_RIGHT => 5,
+                _ => return Err(DecodeError::Inconsistent),
+            };
+            if segments[segment].replace(wire).is_some() {
+                return Err(DecodeError::Inconsistent);
+            }
+        }
+        let [top, top_left, top_right, middle, bottom_left, bottom_right, bottom] = segments;
+        let display = Self {
+            top: top.ok_or(DecodeError::Inconsistent)?,
+            top_left: top_left.ok_or(DecodeError::Inconsistent)?,
+            top_right: top_right.ok_or(DecodeError::Inconsistent)?,
+            middle: middle.ok_or(DecodeError::Inconsistent)?,
+            bottom_left: bottom_left.ok_or(DecodeError::Inconsistent)?,
+            bottom_right: bottom_right.ok_or(DecodeError::Inconsistent)?,
+            bottom: bottom.ok_or(DecodeError::Inconsistent)?,
+        };
+
+        // The frequencies fit, now every pattern has to show a different digit
+        let mut seen = [false; COUNT_DIGITS];
+        for signal in signals {
+            let digit = display
+                .decode(signal)
+                .map_err(|_| DecodeError::Inconsistent)?;
+            if std::mem::replace(&mut seen[usize::from(digit)], true) {
+                return Err(DecodeError::Inconsistent);
+            }
+        }
+        Ok(display)
+    }
 }
 
 #[derive(Default)]
@@ -171,33 +285,6 @@ struct UniqSignals {
     eight: String,
 }
 
-impl UniqSignals {
-    fn into_seven_segments(&self) -> SevenSegmentDisplay {
-        let mut display = SevenSegmentDisplay::default();
-        display.top_right = self.one.chars().nth(0).unwrap();
-        display.bottom_right = self.one.chars().nth(1).unwrap();
-
-        display.top_left = self.four.chars().nth(0).unwrap();
-        display.middle = self.four.chars().nth(2).unwrap();
-        display.top = self
-            .seven
-            .chars()
-            .filter(|it| *it != display.top_right && *it != display.bottom_right)
-            .nth(0)
-            .unwrap();
-        let mut eight_candidates = self.eight.chars().filter(|it| {
-            *it != display.top_right
-                && *it != display.bottom_right
-                && *it != display.top
-                && *it != display.middle
-                && *it != display.top_left
-        });
-        display.bottom_left = eight_candidates.nth(0).unwrap();
-        display.bottom = eight_candidates.nth(1).unwrap();
-        display
-    }
-}
-
 impl From<Vec<&str>> for UniqSignals {
     fn from(signals: Vec<&str>) -> Self {
         let mut ret = Self::default();
@@ -214,7 +301,7 @@ impl From<Vec<&str>> for UniqSignals {
     }
 }
 
-fn parse<'a>(input: &'a str) -> Vec<(Vec<&'a str>, Vec<&'a str>)> {
+fn parse(input: &str) -> Vec<(Vec<&str>, Vec<&str>)> {
     let mut ret = Vec::new();
     let lines = input.split('\n');
     for line in lines {
@@ -247,7 +334,7 @@ dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbc
 bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
 egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
 gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce";
-        let parsed = parse(&input);
+        let parsed = parse(input);
         let part_one_ans = part_one(&parsed);
         assert_eq!(26, part_one_ans);
     }
@@ -256,32 +343,30 @@ gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
     fn test_part_two() {
         let input =
             "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe";
-        let parsed = parse(&input);
-        let uniq: Vec<&str> = parsed
+        let parsed = parse(input);
+        let display = SevenSegmentDisplay::deduce(&parsed[0].0).unwrap();
+        let digits: Vec<u8> = parsed[0]
+            .1
             .iter()
-            .flat_map(|it| it.0.as_slice())
-            .map(|it| *it)
+            .map(|output| display.decode(output).unwrap())
             .collect();
-        let uniq: UniqSignals = uniq.into();
-        assert_eq!("be", uniq.one);
-        assert_eq!("cgeb", uniq.four);
-        assert_eq!("edb", uniq.seven);
-        assert_eq!("cfbegad", uniq.eight);
-        let display = uniq.into_seven_segments();
-        for output in &parsed[0].1 {
-            println!("{} {}", output, display.decode(output));
-        }
+        assert_eq!(vec![8, 3, 9, 4], digits);
+        assert_eq!(Ok(8394), part_two(&parsed));
+
+        let input = std::fs::read_to_string("input").unwrap();
+        let parsed = parse(&input);
+        assert!(part_two(&parsed).is_ok());
     }
 
     #[test]
     fn test_get_uniq_singals() {
         let input =
             "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe";
-        let parsed = parse(&input);
+        let parsed = parse(input);
         let uniq: Vec<&str> = parsed
             .iter()
             .flat_map(|it| it.0.as_slice())
-            .map(|it| *it)
+            .copied()
             .collect();
         let uniq: UniqSignals = uniq.into();
         assert_eq!("be", uniq.one);
@@ -294,27 +379,94 @@ gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
     fn test_get_seven_segment() {
         let input =
             "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe";
-        let parsed = parse(&input);
+        let parsed = parse(input);
         let uniq: Vec<&str> = parsed
             .iter()
             .flat_map(|it| it.0.as_slice())
-            .map(|it| *it)
+            .copied()
             .collect();
         let uniq: UniqSignals = uniq.into();
         assert_eq!("be", uniq.one);
         assert_eq!("cgeb", uniq.four);
         assert_eq!("edb", uniq.seven);
         assert_eq!("cfbegad", uniq.eight);
-        let seven_segment = uniq.into_seven_segments();
+        let seven_segment = SevenSegmentDisplay::deduce(&parsed[0].0).unwrap();
         assert_eq!('b', seven_segment.top_right);
         assert_eq!('e', seven_segment.bottom_right);
 
-        assert_eq!('c', seven_segment.top_left);
-        assert_eq!('e', seven_segment.middle);
+        assert_eq!('g', seven_segment.top_left);
+        assert_eq!('c', seven_segment.middle);
 
         assert_eq!('d', seven_segment.top);
-        assert_eq!('f', seven_segment.bottom_left);
-        assert_eq!('a', seven_segment.bottom);
-        dbg!(seven_segment);
+        assert_eq!('a', seven_segment.bottom_left);
+        assert_eq!('f', seven_segment.bottom);
+    }
+
+    #[test]
+    fn test_deduce_any_order() {
+        // The canonical wiring, with the letters of every pattern shuffled
+        let signals = [
+            "gfecba", "fc", "dgcea", "gfdac", "dfcb", "fgdba", "gfdbae", "fca", "gfedcba", "gbfdca",
+        ];
+        let display = SevenSegmentDisplay::deduce(&signals).unwrap();
+        assert_eq!(
+            SevenSegmentDisplay {
+                top: 'a',
+                top_left: 'b',
+                top_right: 'c',
+                middle: 'd',
+                bottom_left: 'e',
+                bottom_right: 'f',
+                bottom: 'g',
+            },
+            display
+        );
+        let digits: Vec<u8> = signals
+            .iter()
+            .map(|signal| display.decode(signal).unwrap())
+            .collect();
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9], digits);
+    }
+
+    #[test]
+    fn test_deduce_errors() {
+        let signals = [
+            "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+        ];
+        assert!(SevenSegmentDisplay::deduce(&signals).is_ok());
+        assert_eq!(
+            Err(DecodeError::PatternCount(9)),
+            SevenSegmentDisplay::deduce(&signals[..9])
+        );
+
+        let mut invalid = signals;
+        invalid[0] = "abcefh";
+        assert_eq!(
+            Err(DecodeError::InvalidWire('h')),
+            SevenSegmentDisplay::deduce(&invalid)
+        );
+
+        // Two patterns for `1`, none for `0`
+        let mut twice = signals;
+        twice[0] = "cf";
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            SevenSegmentDisplay::deduce(&twice)
+        );
+
+        // `2` and `5` swapped for patterns with the right frequencies but no digit
+        let mut swapped = signals;
+        swapped[2] = "abceg";
+        swapped[5] = "acdfg";
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            SevenSegmentDisplay::deduce(&swapped)
+        );
+
+        let display = SevenSegmentDisplay::deduce(&signals).unwrap();
+        assert_eq!(
+            Err(DecodeError::UnknownPattern("ab".to_string())),
+            display.decode("ab")
+        );
     }
 }
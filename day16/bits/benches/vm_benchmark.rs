@@ -0,0 +1,19 @@
+use bits::{apply_ops, parse_bit_stream, Program};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const INPUT_FN: &str = "input";
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let contents = std::fs::read_to_string(INPUT_FN).unwrap();
+    let packet = parse_bit_stream(contents.trim().try_into().unwrap()).unwrap();
+    let program = Program::compile(&packet);
+
+    c.bench_function("apply_ops", |b| b.iter(|| apply_ops(black_box(&packet))));
+    c.bench_function("vm", |b| b.iter(|| black_box(&program).run()));
+    c.bench_function("compile", |b| {
+        b.iter(|| Program::compile(black_box(&packet)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
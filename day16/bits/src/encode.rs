@@ -0,0 +1,355 @@
+//! Serializing `BITS` packet trees back into a stream of bits
+
+use crate::{
+    fold, walk, BitStream, LiteralValue, Operand, Packet, PacketFold, PacketVisitor, Payload,
+    BITS_CONT, BITS_LEN_TYP, BITS_LITERAL_VAL, BITS_SUB_PACKET_COUNT, BITS_SUB_PACKET_LENGTH,
+    BITS_TYP, BITS_VERSION, LENGTH_TYP_LENGTH_IN_BITS, LENGTH_TYP_NUM_SUB_PACKETS,
+    LITERAL_PACKET_TYP,
+};
+
+/// The way an operator packet describes it's sub packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthTyp {
+    /// The sub packets are described by their total length in bits
+    LengthInBits,
+
+    /// The sub packets are described by their number
+    NumSubPackets,
+}
+
+impl From<LengthTyp> for u8 {
+    fn from(typ: LengthTyp) -> Self {
+        match typ {
+            LengthTyp::LengthInBits => LENGTH_TYP_LENGTH_IN_BITS,
+            LengthTyp::NumSubPackets => LENGTH_TYP_NUM_SUB_PACKETS,
+        }
+    }
+}
+
+/// Errors that can occur while encoding a packet tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    /// The version does not fit into the `version` field
+    VersionOverflow(u8),
+
+    /// The sub packets are too long to fit into the `sub packet length` field
+    SubPacketLengthOverflow(usize),
+
+    /// There are too many sub packets to fit into the `sub packet count` field
+    SubPacketCountOverflow(usize),
+
+    /// The type id of a custom operation does not fit into the `type` field
+    /// or is reserved for `literal` packets
+    InvalidOperand(u8),
+}
+
+impl BitStream {
+    /// Append the lowest `bits` bits of `val` to the end of this stream
+    pub(crate) fn emit(&mut self, val: u128, bits: usize) {
+        for shift in (0..bits).rev() {
+            self.backing.push((val >> shift) & 1 == 1);
+        }
+    }
+
+    /// Render the whole stream as a hex string, padding the last byte with zero bits
+    pub fn to_hex(&self) -> String {
+        let mut ret: String = self
+            .backing
+            .chunks(4)
+            .map(|nibble| {
+                let val = nibble
+                    .iter()
+                    .fold(0u32, |acc, bit| (acc << 1) | *bit as u32);
+                let val = val << (4 - nibble.len());
+                std::char::from_digit(val, 16)
+                    .expect("a nibble is always a valid hex digit")
+                    .to_ascii_uppercase()
+            })
+            .collect();
+        if !ret.len().is_multiple_of(2) {
+            ret.push('0');
+        }
+        ret
+    }
+}
+
+/// Returns whether `val` fits into a field of `bits` bits
+fn fits(val: usize, bits: usize) -> bool {
+    val < 1 << bits
+}
+
+/// Appends the bits of each packet of a tree to a stream while walking it.
+/// The length field of an operator packet is filled in once all it's sub packets are encoded
+struct Encoder<'a, F> {
+    /// The encoded bits
+    out: BitStream,
+
+    /// Decides the length typ of each operator packet
+    choose: &'a mut F,
+
+    /// The position and length typ of the length field of each operator packet entered,
+    /// but not left yet
+    len_fields: Vec<(usize, LengthTyp)>,
+
+    /// The first error encountered, after which the rest of the tree is skipped
+    error: Option<EncodingError>,
+}
+
+impl<F> Encoder<'_, F> {
+    /// Append the header and any literal value of `packet`,
+    /// reserving the length field of operator packets
+    fn encode_enter<L: LiteralValue>(&mut self, packet: &Packet<L>) -> Result<(), EncodingError>
+    where
+        F: FnMut(&Packet<L>) -> LengthTyp,
+    {
+        if !fits(packet.version.into(), BITS_VERSION) {
+            return Err(EncodingError::VersionOverflow(packet.version));
+        }
+        self.out.emit(packet.version.into(), BITS_VERSION);
+
+        match &packet.payload {
+            Payload::Literal(val) => {
+                self.out.emit(LITERAL_PACKET_TYP.into(), BITS_TYP);
+                let groups = val.groups();
+                for (idx, group) in groups.iter().enumerate() {
+                    self.out.emit((idx + 1 < groups.len()).into(), BITS_CONT);
+                    self.out.emit((*group).into(), BITS_LITERAL_VAL);
+                }
+            }
+            Payload::Op(op, _) => {
+                let typ = u8::from(op);
+                if !fits(typ.into(), BITS_TYP) || typ == LITERAL_PACKET_TYP {
+                    return Err(EncodingError::InvalidOperand(typ));
+                }
+                self.out.emit(typ.into(), BITS_TYP);
+                let len_typ = (self.choose)(packet);
+                self.out.emit(u8::from(len_typ).into(), BITS_LEN_TYP);
+                self.len_fields.push((self.out.backing.len(), len_typ));
+                self.out.emit(0, len_field_bits(len_typ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in the length field of the operator packet `packet`
+    fn encode_leave<L>(&mut self, packet: &Packet<L>) -> Result<(), EncodingError> {
+        if !matches!(packet.payload, Payload::Op(..)) {
+            return Ok(());
+        }
+        let (pos, len_typ) = self
+            .len_fields
+            .pop()
+            .expect("every operator packet left has been entered");
+        let bits = len_field_bits(len_typ);
+        let val = match len_typ {
+            LengthTyp::LengthInBits => {
+                let len = self.out.backing.len() - pos - bits;
+                if !fits(len, bits) {
+                    return Err(EncodingError::SubPacketLengthOverflow(len));
+                }
+                len
+            }
+            LengthTyp::NumSubPackets => {
+                let count = packet.childs().len();
+                if !fits(count, bits) {
+                    return Err(EncodingError::SubPacketCountOverflow(count));
+                }
+                count
+            }
+        };
+        for (idx, shift) in (0..bits).rev().enumerate() {
+            self.out.backing.set(pos + idx, (val >> shift) & 1 == 1);
+        }
+        Ok(())
+    }
+}
+
+impl<L: LiteralValue, F: FnMut(&Packet<L>) -> LengthTyp> PacketVisitor<L> for Encoder<'_, F> {
+    fn enter(&mut self, packet: &Packet<L>, _depth: usize) {
+        if self.error.is_none() {
+            self.error = self.encode_enter(packet).err();
+        }
+    }
+
+    fn leave(&mut self, packet: &Packet<L>, _depth: usize) {
+        if self.error.is_none() {
+            self.error = self.encode_leave(packet).err();
+        }
+    }
+}
+
+/// The number of bits of the length field for `len_typ`
+fn len_field_bits(len_typ: LengthTyp) -> usize {
+    match len_typ {
+        LengthTyp::LengthInBits => BITS_SUB_PACKET_LENGTH,
+        LengthTyp::NumSubPackets => BITS_SUB_PACKET_COUNT,
+    }
+}
+
+/// Encode a `BITS` packet tree into a stream of bits.
+/// `choose` decides the length typ of each operator packet in the tree
+pub fn encode_packet_with<L: LiteralValue, F: FnMut(&Packet<L>) -> LengthTyp>(
+    packet: &Packet<L>,
+    mut choose: F,
+) -> Result<BitStream, EncodingError> {
+    let mut encoder = Encoder {
+        out: BitStream::empty(),
+        choose: &mut choose,
+        len_fields: Vec::new(),
+        error: None,
+    };
+    walk(packet, &mut encoder);
+    match encoder.error {
+        Some(e) => Err(e),
+        None => Ok(encoder.out),
+    }
+}
+
+/// Encode a `BITS` packet tree into a stream of bits,
+/// using `len_typ` for every operator packet in the tree
+pub fn encode_packet<L: LiteralValue>(
+    packet: &Packet<L>,
+    len_typ: LengthTyp,
+) -> Result<BitStream, EncodingError> {
+    encode_packet_with(packet, |_| len_typ)
+}
+
+/// Encode a `BITS` packet tree into a hex transmission,
+/// using `len_typ` for every operator packet in the tree
+pub fn encode_hex<L: LiteralValue>(
+    packet: &Packet<L>,
+    len_typ: LengthTyp,
+) -> Result<String, EncodingError> {
+    encode_packet(packet, len_typ).map(|bits| bits.to_hex())
+}
+
+/// Calculates the number of bits a packet tree gets encoded into
+struct EncodedLen(LengthTyp);
+
+impl<L: LiteralValue> PacketFold<L> for EncodedLen {
+    type Output = usize;
+
+    fn literal(&mut self, _packet: &Packet<L>, val: L) -> usize {
+        BITS_VERSION + BITS_TYP + val.groups().len() * (BITS_CONT + BITS_LITERAL_VAL)
+    }
+
+    fn op(&mut self, _packet: &Packet<L>, _op: Operand, childs: Vec<usize>) -> usize {
+        let len_field = len_field_bits(self.0);
+        BITS_VERSION + BITS_TYP + BITS_LEN_TYP + len_field + childs.into_iter().sum::<usize>()
+    }
+}
+
+/// The number of bits `packet` gets encoded into,
+/// using `len_typ` for every operator packet in the tree.
+/// Does not check whether the tree can be encoded at all
+pub fn encoded_len<L: LiteralValue + Clone>(packet: &Packet<L>, len_typ: LengthTyp) -> usize {
+    fold(packet, &mut EncodedLen(len_typ))
+}
+
+impl From<&Operand> for u8 {
+    fn from(op: &Operand) -> Self {
+        match op {
+            Operand::Sum => crate::SUM_PACKET_TYP,
+            Operand::Product => crate::PRODUCT_PACKET_TYPE,
+            Operand::Minimum => crate::MINIMUM_PACKET_TYPE,
+            Operand::Maximum => crate::MAXIMUM_PACKET_TYPE,
+            Operand::GreaterThan => crate::GREATER_THAN_PACKET_TYPE,
+            Operand::LessThan => crate::LESS_THAN_PACKET_TYPE,
+            Operand::Equals => crate::EQ_PACKET_TYPE,
+            Operand::Custom(typ) => *typ,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::EXAMPLES;
+    use crate::{apply_ops, parse_bit_stream};
+
+    /// Decode a hex transmission, panicking on errors
+    fn decode(s: &str) -> Packet {
+        parse_bit_stream(s.try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encode_literal() {
+        let packet: Packet = Packet::new_literal_packet(6, 2021);
+        assert_eq!(
+            "D2FE28",
+            encode_hex(&packet, LengthTyp::LengthInBits).unwrap()
+        );
+
+        let packet = Packet::new_literal_packet(0, 0);
+        let bits = encode_packet(&packet, LengthTyp::LengthInBits).unwrap();
+        assert_eq!(11, bits.backing.len());
+        assert_eq!(packet, parse_bit_stream(bits).unwrap());
+    }
+
+    #[test]
+    fn test_encode_bit_exact() {
+        for (s, len_typ) in [
+            ("38006F45291200", LengthTyp::LengthInBits),
+            ("EE00D40C823060", LengthTyp::NumSubPackets),
+        ] {
+            let packet = decode(s);
+            assert_eq!(s, encode_hex(&packet, len_typ).unwrap());
+        }
+
+        let packet = decode("8A004A801A8002F478");
+        let mut typs = vec![
+            LengthTyp::NumSubPackets,
+            LengthTyp::NumSubPackets,
+            LengthTyp::LengthInBits,
+        ]
+        .into_iter();
+        let bits = encode_packet_with(&packet, |_| typs.next().unwrap()).unwrap();
+        assert_eq!("8A004A801A8002F478", bits.to_hex());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let examples = EXAMPLES.iter().map(|(s, _)| *s);
+        for s in examples.chain([
+            "620080001611562C8802118E34",
+            "A0016C880162017C3686B18A3D4780",
+        ]) {
+            let packet = decode(s);
+            for len_typ in [LengthTyp::LengthInBits, LengthTyp::NumSubPackets] {
+                let bits = encode_packet(&packet, len_typ).unwrap();
+                assert_eq!(bits.backing.len(), encoded_len(&packet, len_typ));
+                let decoded = parse_bit_stream(bits).unwrap();
+                assert_eq!(packet, decoded);
+                assert_eq!(apply_ops(&packet), apply_ops(&decoded));
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let packet: Packet = Packet::new_literal_packet(8, 1);
+        assert_eq!(
+            Err(EncodingError::VersionOverflow(8)),
+            encode_hex(&packet, LengthTyp::LengthInBits)
+        );
+
+        let packet: Packet = Packet::new_op_packet(0, Operand::Custom(LITERAL_PACKET_TYP), vec![]);
+        assert_eq!(
+            Err(EncodingError::InvalidOperand(LITERAL_PACKET_TYP)),
+            encode_hex(&packet, LengthTyp::LengthInBits)
+        );
+
+        let childs = (0..3000)
+            .map(|_| Packet::new_literal_packet(0, 0))
+            .collect();
+        let packet: Packet = Packet::new_op_packet(0, Operand::Sum, childs);
+        assert_eq!(
+            Err(EncodingError::SubPacketCountOverflow(3000)),
+            encode_hex(&packet, LengthTyp::NumSubPackets)
+        );
+        assert_eq!(
+            Err(EncodingError::SubPacketLengthOverflow(3000 * 11)),
+            encode_hex(&packet, LengthTyp::LengthInBits)
+        );
+    }
+}
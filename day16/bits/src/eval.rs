@@ -0,0 +1,235 @@
+//! Evaluation of `BITS` packet trees, wrapping, overflow-checked or arbitrary-precision
+
+use crate::{fold, Operand, OperatorRegistry, Packet, PacketFold};
+use num_bigint::BigUint;
+
+/// Errors that can occur while evaluating a packet tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// An intermediate result does not fit into a `u128`
+    Overflow,
+
+    /// An operation got a number of sub packets it does not accept.
+    /// Holds the number of sub packets found
+    InvalidOperandCount(Operand, usize),
+
+    /// No custom operation is registered for the type id
+    UnknownOperator(u8),
+
+    /// A custom operation has no result for the values of it's sub packets
+    Undefined(Operand),
+}
+
+/// Evaluates a packet tree, wrapping around if a result does not fit into a `u128`.
+/// Custom operations and comparisons without exactly two operands evaluate to `0`
+struct Evaluator;
+
+impl PacketFold for Evaluator {
+    type Output = u128;
+
+    fn literal(&mut self, _packet: &Packet, val: u128) -> u128 {
+        val
+    }
+
+    fn op(&mut self, _packet: &Packet, op: Operand, childs: Vec<u128>) -> u128 {
+        wrapping_op(op, &childs)
+    }
+}
+
+/// Apply `op` on the values of the sub packets `vals`, wrapping around on overflow.
+/// Custom operations and comparisons without exactly two operands evaluate to `0`
+pub(crate) fn wrapping_op(op: Operand, vals: &[u128]) -> u128 {
+    match op {
+        Operand::Sum => vals.iter().fold(0, |acc, x| acc.wrapping_add(*x)),
+        Operand::Product => vals.iter().fold(1, |acc, x| acc.wrapping_mul(*x)),
+        Operand::Minimum => vals.iter().copied().min().unwrap_or(0),
+        Operand::Maximum => vals.iter().copied().max().unwrap_or(0),
+        Operand::Custom(_) => 0,
+        op => match vals {
+            [a, b] => compare(op, a, b) as u128,
+            _ => 0,
+        },
+    }
+}
+
+/// Apply the operations of this packet and it's sub-packets, wrapping around on overflow.
+/// Packets with custom operations and comparisons without exactly two operands
+/// evaluate to `0`, see `try_apply_ops` and `OperatorRegistry::evaluate` for checked evaluation
+pub fn apply_ops(packet: &Packet) -> u128 {
+    fold(packet, &mut Evaluator)
+}
+
+/// Return the two operands of a comparison packet
+fn comparison_operands<T>(op: Operand, childs: Vec<T>) -> Result<(T, T), EvalError> {
+    let len = childs.len();
+    let mut childs = childs.into_iter();
+    match (childs.next(), childs.next(), childs.next()) {
+        (Some(a), Some(b), None) => Ok((a, b)),
+        _ => Err(EvalError::InvalidOperandCount(op, len)),
+    }
+}
+
+/// Evaluates a packet tree, failing if a result does not fit into a `u128`
+struct CheckedEvaluator<'a> {
+    /// The registry holding the custom operations
+    registry: &'a OperatorRegistry,
+}
+
+impl PacketFold for CheckedEvaluator<'_> {
+    type Output = Result<u128, EvalError>;
+
+    fn literal(&mut self, _packet: &Packet, val: u128) -> Self::Output {
+        Ok(val)
+    }
+
+    fn op(&mut self, _packet: &Packet, op: Operand, childs: Vec<Self::Output>) -> Self::Output {
+        let childs = childs.into_iter().collect::<Result<Vec<_>, _>>()?;
+        checked_op(self.registry, op, &childs)
+    }
+}
+
+/// Apply `op` on the values of the sub packets `vals`,
+/// failing if the result does not fit into a `u128`.
+/// Custom operations are evaluated with the functions registered in `registry`
+pub(crate) fn checked_op(
+    registry: &OperatorRegistry,
+    op: Operand,
+    vals: &[u128],
+) -> Result<u128, EvalError> {
+    match op {
+        Operand::Sum => vals.iter().try_fold(0u128, |acc, x| {
+            acc.checked_add(*x).ok_or(EvalError::Overflow)
+        }),
+        Operand::Product => vals.iter().try_fold(1u128, |acc, x| {
+            acc.checked_mul(*x).ok_or(EvalError::Overflow)
+        }),
+        Operand::Minimum => Ok(vals.iter().copied().min().unwrap_or(0)),
+        Operand::Maximum => Ok(vals.iter().copied().max().unwrap_or(0)),
+        Operand::Custom(typ) => {
+            let custom = registry
+                .custom(typ)
+                .ok_or(EvalError::UnknownOperator(typ))?;
+            if !custom.arity.accepts(vals.len()) {
+                return Err(EvalError::InvalidOperandCount(op, vals.len()));
+            }
+            (custom.eval)(vals).ok_or(EvalError::Undefined(op))
+        }
+        op => match vals {
+            [a, b] => Ok(compare(op, a, b) as u128),
+            _ => Err(EvalError::InvalidOperandCount(op, vals.len())),
+        },
+    }
+}
+
+/// Apply the operations of this packet and it's sub-packets,
+/// failing instead of wrapping around if a result does not fit into a `u128`
+pub fn try_apply_ops(packet: &Packet) -> Result<u128, EvalError> {
+    OperatorRegistry::default().evaluate(packet)
+}
+
+impl OperatorRegistry {
+    /// Apply the operations of this packet and it's sub-packets,
+    /// evaluating custom operations with the functions registered here.
+    /// Fails instead of wrapping around if a result does not fit into a `u128`
+    pub fn evaluate(&self, packet: &Packet) -> Result<u128, EvalError> {
+        fold(packet, &mut CheckedEvaluator { registry: self })
+    }
+}
+
+/// Evaluates a packet tree with arbitrary-precision integers.
+/// Custom operations can not be evaluated
+struct BigEvaluator;
+
+impl<L: Into<BigUint>> PacketFold<L> for BigEvaluator {
+    type Output = Result<BigUint, EvalError>;
+
+    fn literal(&mut self, _packet: &Packet<L>, val: L) -> Self::Output {
+        Ok(val.into())
+    }
+
+    fn op(&mut self, _packet: &Packet<L>, op: Operand, childs: Vec<Self::Output>) -> Self::Output {
+        let childs = childs.into_iter().collect::<Result<Vec<_>, _>>()?;
+        match op {
+            Operand::Sum => Ok(childs.into_iter().sum()),
+            Operand::Product => Ok(childs.into_iter().product()),
+            Operand::Minimum => Ok(childs.into_iter().min().unwrap_or_default()),
+            Operand::Maximum => Ok(childs.into_iter().max().unwrap_or_default()),
+            Operand::Custom(typ) => Err(EvalError::UnknownOperator(typ)),
+            op => {
+                let (a, b) = comparison_operands(op, childs)?;
+                Ok(BigUint::from(compare(op, a, b) as u8))
+            }
+        }
+    }
+}
+
+/// Apply the operations of this packet and it's sub-packets,
+/// calculating with arbitrary-precision integers.
+/// Works for packet trees with any type of literal values, including `BigUint`
+pub fn apply_ops_big<L: Clone + Into<BigUint>>(packet: &Packet<L>) -> Result<BigUint, EvalError> {
+    fold(packet, &mut BigEvaluator)
+}
+
+/// Apply the comparison `op` on `a` and `b`
+fn compare<T: Ord>(op: Operand, a: T, b: T) -> bool {
+    match op {
+        Operand::GreaterThan => a > b,
+        Operand::LessThan => a < b,
+        Operand::Equals => a == b,
+        _ => unreachable!("{:?} is not a comparison", op),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_bit_stream;
+    use crate::test::EXAMPLES;
+
+    #[test]
+    fn test_checked_matches_unchecked() {
+        for (s, expected) in EXAMPLES {
+            let packet = parse_bit_stream(s.try_into().unwrap()).unwrap();
+            let res = apply_ops(&packet);
+            assert_eq!(expected, res, "{}", s);
+            assert_eq!(Ok(res), try_apply_ops(&packet));
+            assert_eq!(Ok(BigUint::from(res)), apply_ops_big(&packet));
+        }
+    }
+
+    #[test]
+    fn test_overflow() {
+        let big = 1u128 << 64;
+        let packet: Packet = format!("(product (lit {}) (lit {}))", big, big)
+            .parse()
+            .unwrap();
+        assert_eq!(Err(EvalError::Overflow), try_apply_ops(&packet));
+        assert_eq!(Ok(BigUint::from(1u8) << 128), apply_ops_big(&packet));
+
+        let packet: Packet = format!("(sum (lit {}) (lit 1))", u128::MAX)
+            .parse()
+            .unwrap();
+        assert_eq!(Err(EvalError::Overflow), try_apply_ops(&packet));
+        assert_eq!(Ok(BigUint::from(u128::MAX) + 1u8), apply_ops_big(&packet));
+
+        let packet: Packet = format!("(gt (product (lit {}) (lit 2)) (lit 1))", u128::MAX)
+            .parse()
+            .unwrap();
+        assert_eq!(Err(EvalError::Overflow), try_apply_ops(&packet));
+        assert_eq!(Ok(BigUint::from(1u8)), apply_ops_big(&packet));
+    }
+
+    #[test]
+    fn test_invalid_operand_count() {
+        let packet =
+            Packet::new_op_packet(0, Operand::Equals, vec![Packet::new_literal_packet(0, 1)]);
+        assert_eq!(
+            Err(EvalError::InvalidOperandCount(Operand::Equals, 1)),
+            try_apply_ops(&packet)
+        );
+        assert_eq!(
+            Err(EvalError::InvalidOperandCount(Operand::Equals, 1)),
+            apply_ops_big(&packet)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Exporting `BITS` packet trees as Graphviz DOT graphs and JSON documents
+//!
+//! Every packet is annotated with the value it evaluates to with `apply_ops`.
+
+use crate::eval::wrapping_op;
+use crate::expr::LITERAL_NAME;
+use crate::{walk, Packet, PacketVisitor, Payload, LITERAL_PACKET_TYP};
+use std::fmt::Write;
+
+/// The values of the packets that have been left, but whose parent has not
+#[derive(Debug, Default)]
+struct ValueStack(Vec<u128>);
+
+impl ValueStack {
+    /// Evaluate `packet` from the values of it's sub packets like `apply_ops`
+    fn leave(&mut self, packet: &Packet) -> u128 {
+        let val = match &packet.payload {
+            Payload::Literal(val) => *val,
+            Payload::Op(op, childs) => {
+                let args = self.0.len() - childs.len();
+                let val = wrapping_op(*op, &self.0[args..]);
+                self.0.truncate(args);
+                val
+            }
+        };
+        self.0.push(val);
+        val
+    }
+}
+
+/// The name of the type of `packet`, as used in the expression syntax
+fn typ_name(packet: &Packet) -> String {
+    match &packet.payload {
+        Payload::Literal(_) => LITERAL_NAME.to_string(),
+        Payload::Op(op, _) => op.to_string(),
+    }
+}
+
+/// Renders a packet tree as a DOT graph
+#[derive(Debug, Default)]
+struct DotPrinter {
+    /// The rendered graph
+    out: String,
+
+    /// The id of the next packet entered
+    next_id: usize,
+
+    /// The ids of the packets that have been entered, but not left
+    ids: Vec<usize>,
+
+    /// The values of the packets left so far
+    values: ValueStack,
+}
+
+impl PacketVisitor for DotPrinter {
+    fn enter(&mut self, _packet: &Packet, _depth: usize) {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(parent) = self.ids.last() {
+            writeln!(self.out, "    n{} -> n{};", parent, id).expect("writing to a string");
+        }
+        self.ids.push(id);
+    }
+
+    fn leave(&mut self, packet: &Packet, _depth: usize) {
+        let id = self.ids.pop().expect("every packet left was entered");
+        let val = self.values.leave(packet);
+        let shape = match packet.payload {
+            Payload::Literal(_) => "box",
+            Payload::Op(..) => "ellipse",
+        };
+        writeln!(
+            self.out,
+            "    n{} [shape={}, label=\"v{} {}\\n{}\"];",
+            id,
+            shape,
+            packet.version,
+            typ_name(packet),
+            val
+        )
+        .expect("writing to a string");
+    }
+}
+
+/// Render the packet tree as a Graphviz DOT graph.
+/// Each node is labelled with the version, the operation and the value of it's packet
+pub fn to_dot(packet: &Packet) -> String {
+    let mut printer = DotPrinter::default();
+    walk(packet, &mut printer);
+    format!("digraph packet {{\n{}}}\n", printer.out)
+}
+
+/// Renders a packet tree as a JSON document
+#[derive(Debug, Default)]
+struct JsonPrinter {
+    /// The rendered document
+    out: String,
+
+    /// Whether the next packet entered is preceded by a sibling
+    needs_comma: bool,
+
+    /// The values of the packets left so far
+    values: ValueStack,
+}
+
+impl PacketVisitor for JsonPrinter {
+    fn enter(&mut self, packet: &Packet, _depth: usize) {
+        if self.needs_comma {
+            self.out.push(',');
+        }
+        let typ_id = match &packet.payload {
+            Payload::Literal(_) => LITERAL_PACKET_TYP,
+            Payload::Op(op, _) => op.into(),
+        };
+        write!(
+            self.out,
+            "{{\"version\":{},\"type\":\"{}\",\"type_id\":{},\"span\":",
+            packet.version,
+            typ_name(packet),
+            typ_id
+        )
+        .expect("writing to a string");
+        match packet.span {
+            Some(span) => write!(
+                self.out,
+                "{{\"offset\":{},\"len\":{}}}",
+                span.offset, span.len
+            )
+            .expect("writing to a string"),
+            None => self.out.push_str("null"),
+        }
+        if let Payload::Op(..) = packet.payload {
+            self.out.push_str(",\"sub_packets\":[");
+        }
+        self.needs_comma = false;
+    }
+
+    fn leave(&mut self, packet: &Packet, _depth: usize) {
+        if let Payload::Op(..) = packet.payload {
+            self.out.push(']');
+        }
+        let val = self.values.leave(packet);
+        write!(self.out, ",\"value\":\"{}\"}}", val).expect("writing to a string");
+        self.needs_comma = true;
+    }
+}
+
+/// Render the packet tree as a JSON document.
+/// Each packet is an object holding it's `version`, `type` name, `type_id`,
+/// the `span` it was decoded from or `null`, the `sub_packets` of operations
+/// and it's `value`. Values are strings, as they may exceed the safe range of JSON numbers
+pub fn to_json(packet: &Packet) -> String {
+    let mut printer = JsonPrinter::default();
+    walk(packet, &mut printer);
+    printer.out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_bit_stream, parse_expr, Arity, CustomOperator, OperatorRegistry};
+
+    #[test]
+    fn test_dot() {
+        let packet: Packet = "(sum@1 (lit@2 1) (max@3 (lit@4 2) (lit@5 3)))"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "digraph packet {
+    n0 -> n1;
+    n1 [shape=box, label=\"v2 lit\\n1\"];
+    n0 -> n2;
+    n2 -> n3;
+    n3 [shape=box, label=\"v4 lit\\n2\"];
+    n2 -> n4;
+    n4 [shape=box, label=\"v5 lit\\n3\"];
+    n2 [shape=ellipse, label=\"v3 max\\n3\"];
+    n0 [shape=ellipse, label=\"v1 sum\\n4\"];
+}
+",
+            to_dot(&packet)
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let packet = parse_bit_stream("38006F45291200".try_into().unwrap()).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"version":1,"type":"lt","type_id":6,"span":{"offset":0,"len":49},"sub_packets":["#,
+                r#"{"version":6,"type":"lit","type_id":4,"span":{"offset":22,"len":11},"value":"10"},"#,
+                r#"{"version":2,"type":"lit","type_id":4,"span":{"offset":33,"len":16},"value":"20"}"#,
+                r#"],"value":"1"}"#
+            ),
+            to_json(&packet)
+        );
+
+        let mut registry = OperatorRegistry::new();
+        let op = CustomOperator::new("opaque", Arity::Any, |_| None);
+        registry.register(5, op).unwrap();
+        let packet = parse_expr("(product (sum) (custom5))", &registry).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"version":0,"type":"product","type_id":1,"span":null,"sub_packets":["#,
+                r#"{"version":0,"type":"sum","type_id":0,"span":null,"sub_packets":[],"value":"0"},"#,
+                r#"{"version":0,"type":"custom5","type_id":5,"span":null,"sub_packets":[],"value":"0"}"#,
+                r#"],"value":"0"}"#
+            ),
+            to_json(&packet)
+        );
+    }
+}
@@ -0,0 +1,559 @@
+//! A textual S-expression syntax for `BITS` packet trees
+//!
+//! Literals are written as `(lit 2021)` and operators as `(sum (lit 1) (lit 2))`.
+//! Every packet may carry a version annotation, e.g. `(max@7 (lit@2 1))`.
+//! Packets without an annotation have version `0`.
+//! Custom operations are written by the name they are registered with, e.g. `(xor)`,
+//! or as `custom` followed by their type id, e.g. `(custom5)`,
+//! and only parse if registered as a custom operation.
+//! The names of the built-in operations always parse to the built-in operations.
+
+use crate::{walk, Operand, OperatorRegistry, Packet, PacketVisitor, Payload, BITS_VERSION};
+use std::fmt;
+use std::str::FromStr;
+
+/// The name of a literal packet in the expression syntax
+pub(crate) const LITERAL_NAME: &str = "lit";
+
+/// The prefix of a custom operation, followed by it's type id
+const CUSTOM_PREFIX: &str = "custom";
+
+/// The separator between a packet name and it's version annotation
+const VERSION_SEP: char = '@';
+
+/// Errors that can occur while parsing an expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprError {
+    /// The expression ended earlier than expected
+    EOF,
+
+    /// Encountered a token that is not valid at this position
+    UnexpectedToken(String, usize),
+
+    /// Encountered a packet name that is neither `lit` nor an operator
+    UnknownOperator(String, usize),
+
+    /// Failing to parse a literal value
+    InvalidLiteral(String, usize),
+
+    /// Failing to parse a version annotation
+    InvalidVersion(String, usize),
+
+    /// A version annotation does not fit into the `version` field
+    VersionOutOfRange(u8, usize),
+
+    /// A comparison operator does not have exactly two operands.
+    /// Holds the byte offset of the operator name
+    ComparisonArity(Operand, usize),
+
+    /// There is input left after the expression
+    TrailingInput(usize),
+}
+
+impl fmt::Display for Operand {
+    /// Prints the name of this operation in the expression syntax
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Sum => write!(f, "sum"),
+            Operand::Product => write!(f, "product"),
+            Operand::Minimum => write!(f, "min"),
+            Operand::Maximum => write!(f, "max"),
+            Operand::GreaterThan => write!(f, "gt"),
+            Operand::LessThan => write!(f, "lt"),
+            Operand::Equals => write!(f, "eq"),
+            Operand::Custom(typ) => write!(f, "{}{}", CUSTOM_PREFIX, typ),
+        }
+    }
+}
+
+impl Operand {
+    /// Find the operation with the name `name` in the expression syntax.
+    /// A custom operation is only found if it is registered in `registry`
+    fn from_name(name: &str, registry: &OperatorRegistry) -> Option<Self> {
+        Some(match name {
+            "sum" => Operand::Sum,
+            "product" => Operand::Product,
+            "min" => Operand::Minimum,
+            "max" => Operand::Maximum,
+            "gt" => Operand::GreaterThan,
+            "lt" => Operand::LessThan,
+            "eq" => Operand::Equals,
+            _ => match registry.find_custom(name) {
+                Some(typ) => Operand::Custom(typ),
+                None => {
+                    let typ: u8 = name.strip_prefix(CUSTOM_PREFIX)?.parse().ok()?;
+                    registry.custom(typ)?;
+                    Operand::Custom(typ)
+                }
+            },
+        })
+    }
+}
+
+/// A token of the expression syntax
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// An opening parenthesis
+    Open,
+
+    /// A closing parenthesis
+    Close,
+
+    /// A name or a number
+    Atom(&'a str),
+}
+
+/// Splits an expression into tokens, remembering the byte offset of each token
+fn tokenize(s: &str) -> Vec<(Token<'_>, usize)> {
+    let mut ret = Vec::new();
+    let mut atom_start = None;
+    for (idx, c) in s.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = atom_start.take() {
+                ret.push((Token::Atom(&s[start..idx]), start));
+            }
+            match c {
+                '(' => ret.push((Token::Open, idx)),
+                ')' => ret.push((Token::Close, idx)),
+                _ => {}
+            }
+        } else if atom_start.is_none() {
+            atom_start = Some(idx);
+        }
+    }
+    if let Some(start) = atom_start {
+        ret.push((Token::Atom(&s[start..]), start));
+    }
+    ret
+}
+
+/// An operator packet whose name has been parsed, but not all of it's sub packets
+struct OpenOperator {
+    /// The version annotation of the packet
+    version: u8,
+
+    /// The operation of the packet
+    op: Operand,
+
+    /// The byte offset of the packet name
+    pos: usize,
+
+    /// The sub packets parsed so far
+    childs: Vec<Packet>,
+}
+
+impl OpenOperator {
+    /// Build the packet once the closing parenthesis is parsed
+    fn finish(self) -> Result<Packet, ExprError> {
+        if self.op.is_comparison() && self.childs.len() != 2 {
+            return Err(ExprError::ComparisonArity(self.op, self.pos));
+        }
+        Ok(Packet::new_op_packet(self.version, self.op, self.childs))
+    }
+}
+
+/// The start of a packet: either a whole `literal` packet,
+/// or an operator packet waiting for it's sub packets
+enum PacketStart {
+    /// A fully parsed `literal` packet
+    Literal(Packet),
+
+    /// An operator packet whose sub packets are parsed next
+    Operator(OpenOperator),
+}
+
+/// A parser over a list of tokens, keeping the operator packets
+/// being parsed on a stack on the heap, so deeply nested expressions
+/// do not overflow the call stack
+struct Parser<'a> {
+    /// The tokens of the expression
+    tokens: Vec<(Token<'a>, usize)>,
+
+    /// The operations custom operator names are resolved with
+    registry: &'a OperatorRegistry,
+
+    /// The index of the next token to parse
+    idx: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Return the next token and advance
+    fn next(&mut self) -> Result<&(Token<'a>, usize), ExprError> {
+        let ret = self.tokens.get(self.idx).ok_or(ExprError::EOF)?;
+        self.idx += 1;
+        Ok(ret)
+    }
+
+    /// Return the next token without advancing
+    fn peek(&self) -> Result<&(Token<'a>, usize), ExprError> {
+        self.tokens.get(self.idx).ok_or(ExprError::EOF)
+    }
+
+    /// Parse the next token as an atom
+    fn atom(&mut self) -> Result<(&'a str, usize), ExprError> {
+        match self.next()? {
+            (Token::Atom(atom), pos) => Ok((atom, *pos)),
+            (token, pos) => Err(unexpected(token, *pos)),
+        }
+    }
+
+    /// Parse a closing parenthesis
+    fn close(&mut self) -> Result<(), ExprError> {
+        match self.next()? {
+            (Token::Close, _) => Ok(()),
+            (token, pos) => Err(unexpected(token, *pos)),
+        }
+    }
+
+    /// Parse the opening parenthesis and name of a packet,
+    /// and the rest of it if it is a `literal` packet
+    fn packet_start(&mut self) -> Result<PacketStart, ExprError> {
+        match self.next()? {
+            (Token::Open, _) => {}
+            (token, pos) => return Err(unexpected(token, *pos)),
+        }
+
+        let (head, pos) = self.atom()?;
+        let (name, version) = match head.split_once(VERSION_SEP) {
+            Some((name, version)) => {
+                let version: u8 = version
+                    .parse()
+                    .map_err(|_| ExprError::InvalidVersion(version.to_string(), pos))?;
+                if version >= 1 << BITS_VERSION {
+                    return Err(ExprError::VersionOutOfRange(version, pos));
+                }
+                (name, version)
+            }
+            None => (head, 0),
+        };
+
+        if name == LITERAL_NAME {
+            let (val, pos) = self.atom()?;
+            let val = val
+                .parse()
+                .map_err(|_| ExprError::InvalidLiteral(val.to_string(), pos))?;
+            self.close()?;
+            return Ok(PacketStart::Literal(Packet::new_literal_packet(
+                version, val,
+            )));
+        }
+
+        let op = Operand::from_name(name, self.registry)
+            .ok_or_else(|| ExprError::UnknownOperator(name.to_string(), pos))?;
+        Ok(PacketStart::Operator(OpenOperator {
+            version,
+            op,
+            pos,
+            childs: Vec::new(),
+        }))
+    }
+
+    /// Parse a packet with all it's sub packets
+    fn packet(&mut self) -> Result<Packet, ExprError> {
+        let mut stack: Vec<OpenOperator> = Vec::new();
+        loop {
+            let mut finished = match self.packet_start()? {
+                PacketStart::Literal(packet) => Some(packet),
+                PacketStart::Operator(open) => {
+                    stack.push(open);
+                    None
+                }
+            };
+
+            // Hand finished packets to their parents, closing every parent that ends here
+            loop {
+                if let Some(packet) = finished.take() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.childs.push(packet),
+                        None => return Ok(packet),
+                    }
+                }
+                if self.peek()?.0 != Token::Close {
+                    break;
+                }
+                self.close()?;
+                let open = stack.pop().expect("a packet is open until it is closed");
+                finished = Some(open.finish()?);
+            }
+        }
+    }
+}
+
+/// Build the error for an unexpected token at byte offset `pos`
+fn unexpected(token: &Token<'_>, pos: usize) -> ExprError {
+    let token = match token {
+        Token::Open => "(",
+        Token::Close => ")",
+        Token::Atom(atom) => atom,
+    };
+    ExprError::UnexpectedToken(token.to_string(), pos)
+}
+
+/// Parse the expression `s`, resolving custom operator names with `registry`
+pub fn parse_expr(s: &str, registry: &OperatorRegistry) -> Result<Packet, ExprError> {
+    let mut parser = Parser {
+        tokens: tokenize(s),
+        registry,
+        idx: 0,
+    };
+    let ret = parser.packet()?;
+    match parser.tokens.get(parser.idx) {
+        Some((_, pos)) => Err(ExprError::TrailingInput(*pos)),
+        None => Ok(ret),
+    }
+}
+
+impl FromStr for Packet {
+    type Err = ExprError;
+
+    /// Parses an expression with only the built-in operations
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_expr(s, &OperatorRegistry::default())
+    }
+}
+
+/// Renders a packet tree in the expression syntax
+struct ExprPrinter<'a> {
+    /// The rendered expression
+    out: String,
+
+    /// Whether every sub packet is put on it's own, indented line
+    indent: bool,
+
+    /// The registry custom operations are named by, if any
+    registry: Option<&'a OperatorRegistry>,
+}
+
+impl ExprPrinter<'_> {
+    /// The name `op` is printed as. A custom operation is printed by the name it is
+    /// registered with, if that name parses back to the same operation
+    fn name(&self, op: Operand) -> String {
+        if let (Operand::Custom(typ), Some(registry)) = (op, self.registry) {
+            if let Some(custom) = registry.custom(typ) {
+                let is_atom = !custom.name.is_empty()
+                    && custom.name != LITERAL_NAME
+                    && !custom.name.contains(|c: char| {
+                        c.is_whitespace() || "()".contains(c) || c == VERSION_SEP
+                    });
+                if is_atom && Operand::from_name(&custom.name, registry) == Some(op) {
+                    return custom.name.clone();
+                }
+            }
+        }
+        op.to_string()
+    }
+}
+
+impl<L: fmt::Display> PacketVisitor<L> for ExprPrinter<'_> {
+    fn enter(&mut self, packet: &Packet<L>, depth: usize) {
+        if depth > 0 {
+            match self.indent {
+                true => self.out += &format!("\n{:width$}", "", width = depth * 2),
+                false => self.out.push(' '),
+            }
+        }
+        self.out += &match &packet.payload {
+            Payload::Literal(val) => {
+                format!("({}{}{} {}", LITERAL_NAME, VERSION_SEP, packet.version, val)
+            }
+            Payload::Op(op, _) => {
+                format!("({}{}{}", self.name(*op), VERSION_SEP, packet.version)
+            }
+        };
+    }
+
+    fn leave(&mut self, _packet: &Packet<L>, _depth: usize) {
+        self.out.push(')');
+    }
+}
+
+impl<L: fmt::Display> fmt::Display for Packet<L> {
+    /// Prints the packet tree in the expression syntax with versions annotated.
+    /// The alternate flag (`{:#}`) puts every sub packet on it's own, indented line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = ExprPrinter {
+            out: String::new(),
+            indent: f.alternate(),
+            registry: None,
+        };
+        walk(self, &mut printer);
+        f.write_str(&printer.out)
+    }
+}
+
+/// Render the packet tree in the expression syntax with versions annotated,
+/// like `Display`, but printing custom operations by the name registered in `registry`.
+/// The result parses back into the same packet tree with `parse_expr`
+pub fn to_expr<L: fmt::Display>(packet: &Packet<L>, registry: &OperatorRegistry) -> String {
+    let mut printer = ExprPrinter {
+        out: String::new(),
+        indent: false,
+        registry: Some(registry),
+    };
+    walk(packet, &mut printer);
+    printer.out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        apply_ops, depth_stats, encode_packet, parse_bit_stream, Arity, CustomOperator, Decoder,
+        LengthTyp,
+    };
+
+    #[test]
+    fn test_parse_expr() {
+        let packet: Packet = "(sum (lit 1) (max (lit 2) (lit 3)))".parse().unwrap();
+        assert_eq!(
+            Packet::new_op_packet(
+                0,
+                Operand::Sum,
+                vec![
+                    Packet::new_literal_packet(0, 1),
+                    Packet::new_op_packet(
+                        0,
+                        Operand::Maximum,
+                        vec![
+                            Packet::new_literal_packet(0, 2),
+                            Packet::new_literal_packet(0, 3)
+                        ]
+                    )
+                ]
+            ),
+            packet
+        );
+        assert_eq!(4, apply_ops(&packet));
+
+        let packet: Packet = " ( lt@1\n(lit@6 10)(lit@2 20) ) ".parse().unwrap();
+        let bin = "38006F45291200".try_into().unwrap();
+        assert_eq!(parse_bit_stream(bin).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_parse_expr_errors() {
+        let parse = |s: &str| s.parse::<Packet>().unwrap_err();
+        assert_eq!(ExprError::EOF, parse("(sum (lit 1)"));
+        assert_eq!(ExprError::EOF, parse(""));
+        assert_eq!(
+            ExprError::UnknownOperator("xor".to_string(), 1),
+            parse("(xor (lit 1))")
+        );
+        assert_eq!(
+            ExprError::InvalidLiteral("-1".to_string(), 5),
+            parse("(lit -1)")
+        );
+        assert_eq!(
+            ExprError::InvalidVersion("x".to_string(), 1),
+            parse("(lit@x 1)")
+        );
+        assert_eq!(ExprError::VersionOutOfRange(8, 1), parse("(lit@8 1)"));
+        assert_eq!(ExprError::VersionOutOfRange(9, 6), parse("(sum (lit@9 1))"));
+        assert_eq!(
+            ExprError::ComparisonArity(Operand::Equals, 6),
+            parse("(sum (eq (lit 1) (lit 2) (lit 3)))")
+        );
+        assert_eq!(
+            ExprError::UnexpectedToken("(".to_string(), 5),
+            parse("(lit (lit 1))")
+        );
+        assert_eq!(ExprError::TrailingInput(8), parse("(lit 1) (lit 2)"));
+        assert_eq!(
+            ExprError::UnknownOperator("custom4".to_string(), 1),
+            parse("(custom4 (lit 1))")
+        );
+        assert_eq!(
+            ExprError::UnknownOperator("custom8".to_string(), 1),
+            parse("(custom8 (lit 1))")
+        );
+        assert_eq!(
+            ExprError::UnknownOperator("custom0".to_string(), 1),
+            parse("(custom0 (lit 1))")
+        );
+    }
+
+    #[test]
+    fn test_custom_names() {
+        let mut registry = OperatorRegistry::default();
+        let xor = CustomOperator::new("xor", Arity::Any, |vals| {
+            Some(vals.iter().fold(0, |acc, x| acc ^ x))
+        });
+        let shadowed = CustomOperator::new("sum", Arity::Any, |_| None);
+        let spaced = CustomOperator::new("no op", Arity::Any, |_| None);
+        registry
+            .register(2, xor)
+            .unwrap()
+            .register(3, shadowed)
+            .unwrap()
+            .register(5, spaced)
+            .unwrap();
+
+        let s = "(sum@1 (xor@2 (lit@0 5) (lit@0 3)) (custom2@0) (custom3@0) (custom5@0))";
+        let packet = parse_expr(s, &registry).unwrap();
+        assert_eq!(
+            Packet::new_op_packet(0, Operand::Custom(2), vec![]),
+            packet.childs()[1]
+        );
+        assert_eq!(Ok(6), registry.evaluate(&packet.childs()[0]));
+        // Names that do not parse back to the same operation fall back to the type id
+        let expected = "(sum@1 (xor@2 (lit@0 5) (lit@0 3)) (xor@0) (custom3@0) (custom5@0))";
+        assert_eq!(expected, to_expr(&packet, &registry));
+        assert_eq!(packet, parse_expr(expected, &registry).unwrap());
+        assert!(packet.to_string().contains("(custom2@2 (lit@0 5)"));
+
+        assert_eq!(
+            ExprError::UnknownOperator("xor".to_string(), 1),
+            "(xor)".parse::<Packet>().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 100_000;
+        let s = format!("{}(lit 1){}", "(sum ".repeat(depth), ")".repeat(depth));
+        let packet: Packet = s.parse().unwrap();
+        assert_eq!(depth, depth_stats(&packet).max_depth);
+        assert!(packet == packet.to_string().parse().unwrap());
+
+        let s = format!("{}(lit 1)", "(sum ".repeat(depth));
+        assert_eq!(ExprError::EOF, s.parse::<Packet>().unwrap_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let bin = "EE00D40C823060".try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!("(max@7 (lit@2 1) (lit@4 2) (lit@1 3))", packet.to_string());
+        assert_eq!(
+            "(max@7\n  (lit@2 1)\n  (lit@4 2)\n  (lit@1 3))",
+            format!("{:#}", packet)
+        );
+
+        for s in [
+            "9C0141080250320F1802104A08",
+            "A0016C880162017C3686B18A3D4780",
+        ] {
+            let packet = parse_bit_stream(s.try_into().unwrap()).unwrap();
+            assert_eq!(packet, packet.to_string().parse().unwrap());
+            assert_eq!(packet, format!("{:#}", packet).parse().unwrap());
+        }
+
+        // A custom operation round trips only through a registry that has it
+        let mut registry = OperatorRegistry::default();
+        registry
+            .register(
+                7,
+                CustomOperator::new("first", Arity::AtLeast(1), |vals| Some(vals[0])),
+            )
+            .unwrap();
+        let s = "(custom7@3 (lit@0 1) (sum@1 (lit@2 2)))";
+        let packet = parse_expr(s, &registry).unwrap();
+        assert_eq!(s, packet.to_string());
+        let decoder = Decoder {
+            registry,
+            ..Decoder::default()
+        };
+        let bits = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+        assert_eq!(packet, decoder.decode(bits).unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+//! Constructing a `BitStream` from raw bytes, base64 text or hex nibbles
+
+use crate::{BitStream, ParsingError};
+use bitvec::prelude::*;
+
+/// The alphabet of standard base64, in the order of the values
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The character padding base64 text to a multiple of four characters
+const BASE64_PADDING: char = '=';
+
+/// The number of bits a base64 character encodes
+const BITS_BASE64_CHAR: usize = 6;
+
+/// The number of bits a hex digit encodes
+const BITS_HEX_DIGIT: usize = 4;
+
+impl From<&[u8]> for BitStream {
+    /// Construct a stream from raw bytes, most significant bit first
+    fn from(bytes: &[u8]) -> Self {
+        Self {
+            backing: bytes.view_bits::<Msb0>().to_bitvec(),
+            idx: 0,
+        }
+    }
+}
+
+/// Tries to convert a base64 char into it's value
+fn base64_value(c: char) -> Result<u8, ParsingError> {
+    BASE64_ALPHABET
+        .iter()
+        .position(|x| *x as char == c)
+        .map(|val| val as u8)
+        .ok_or(ParsingError::InvalidBase64Char(c))
+}
+
+impl BitStream {
+    /// Construct a stream from hex digits without requiring whole bytes,
+    /// so the stream may end in the middle of a byte
+    pub fn from_hex_nibbles(s: &str) -> Result<Self, ParsingError> {
+        let mut ret = Self::empty();
+        for c in s.chars() {
+            let nibble = c.to_digit(16).ok_or(ParsingError::InvalidHexDigit(c))?;
+            ret.emit(nibble.into(), BITS_HEX_DIGIT);
+        }
+        Ok(ret)
+    }
+
+    /// Construct a stream from standard base64 text.
+    /// Whitespace is ignored and the trailing `=` padding is optional,
+    /// but if present it has to complete the last group of four chars.
+    /// The bits of the last char past the last whole byte have to be zero
+    pub fn from_base64(s: &str) -> Result<Self, ParsingError> {
+        let s = s.trim_end();
+        let unpadded = s.trim_end_matches(BASE64_PADDING);
+        // Padding fills up the last group of four chars, so it is at most two chars long
+        let padding = s.len() - unpadded.len();
+        if padding > 0 {
+            let chars = s.chars().filter(|c| !c.is_ascii_whitespace()).count();
+            if padding > 2 || chars % 4 != 0 {
+                return Err(ParsingError::InvalidInputLen);
+            }
+        }
+        let s = unpadded;
+        let mut ret = Self::empty();
+        for c in s.chars().filter(|c| !c.is_ascii_whitespace()) {
+            let val = base64_value(c)?;
+            ret.emit(val.into(), BITS_BASE64_CHAR);
+        }
+        // Each group of four chars encodes three bytes, a trailing group only whole bytes
+        if ret.backing.len() % 24 == BITS_BASE64_CHAR {
+            return Err(ParsingError::InvalidInputLen);
+        }
+        // The bits of a trailing group past the last whole byte are always zero
+        let len = ret.backing.len() - ret.backing.len() % 8;
+        if ret.backing[len..].any() {
+            return Err(ParsingError::InvalidInputLen);
+        }
+        ret.backing.truncate(len);
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_bit_stream, Decoder, Packet};
+
+    #[test]
+    fn test_formats_agree() {
+        let expected = parse_bit_stream("9C0141080250320F1802104A08".try_into().unwrap()).unwrap();
+        let bytes = [
+            0x9C, 0x01, 0x41, 0x08, 0x02, 0x50, 0x32, 0x0F, 0x18, 0x02, 0x10, 0x4A, 0x08,
+        ];
+        for bits in [
+            BitStream::from(&bytes[..]),
+            BitStream::from_base64("nAFBCAJQMg8YAhBKCA==").unwrap(),
+            BitStream::from_base64("nAFBCAJQ\nMg8YAhBKCA").unwrap(),
+            BitStream::from_hex_nibbles("9C0141080250320F1802104A08").unwrap(),
+        ] {
+            assert_eq!(expected, parse_bit_stream(bits).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_odd_nibbles() {
+        // A literal packet without the padding to a whole byte
+        let bits = BitStream::from_hex_nibbles("D23084200").unwrap();
+        assert_eq!(36, bits.backing.len());
+        assert_eq!(
+            Packet::new_literal_packet(6, 1 << 20),
+            Decoder::strict().decode(bits).unwrap()
+        );
+        assert!(matches!(
+            BitStream::try_from("D23084200"),
+            Err(ParsingError::InvalidInputLen)
+        ));
+        assert!(matches!(
+            BitStream::from_hex_nibbles("D2G"),
+            Err(ParsingError::InvalidHexDigit('G'))
+        ));
+    }
+
+    #[test]
+    fn test_base64_errors() {
+        assert!(matches!(
+            BitStream::from_base64("0v4o!"),
+            Err(ParsingError::InvalidBase64Char('!'))
+        ));
+        assert!(matches!(
+            BitStream::from_base64("0v4oA"),
+            Err(ParsingError::InvalidInputLen)
+        ));
+        assert_eq!(24, BitStream::from_base64("0v4o").unwrap().backing.len());
+        assert_eq!(16, BitStream::from_base64("0v4=").unwrap().backing.len());
+        assert_eq!(8, BitStream::from_base64("0g==\n").unwrap().backing.len());
+        for s in [
+            "AAAA====", "0v4o=", "0v===", "0v=", "0v4==", "===", "0v5=", "0v5", "0v==",
+        ] {
+            assert!(
+                matches!(
+                    BitStream::from_base64(s),
+                    Err(ParsingError::InvalidInputLen)
+                ),
+                "{}",
+                s
+            );
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Generating random `BITS` packet trees and corrupted transmissions
+//!
+//! The generator is deterministic for a given seed,
+//! so failing cases can be reproduced from the seed alone.
+
+use crate::{encode_packet_with, BitStream, EncodingError, LengthTyp, Operand, Packet};
+
+/// All built-in operations
+const OPERANDS: [Operand; 7] = [
+    Operand::Sum,
+    Operand::Product,
+    Operand::Minimum,
+    Operand::Maximum,
+    Operand::GreaterThan,
+    Operand::LessThan,
+    Operand::Equals,
+];
+
+/// Generates random packet trees and transmissions
+#[derive(Debug, Clone)]
+pub struct PacketGenerator {
+    /// The state of the pseudo random number generator
+    state: u64,
+
+    /// The maximum nesting depth of generated trees
+    pub max_depth: usize,
+
+    /// The maximum number of sub packets of generated operator packets
+    pub max_childs: usize,
+
+    /// The maximum number of significant bits of generated literals
+    pub max_literal_bits: u32,
+
+    /// Whether comparison packets always get exactly two sub packets
+    pub strict_arity: bool,
+}
+
+impl PacketGenerator {
+    /// Construct a new generator from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            max_depth: 4,
+            max_childs: 3,
+            max_literal_bits: u128::BITS,
+            strict_arity: true,
+        }
+    }
+
+    /// The next pseudo random number, using `SplitMix64`
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A pseudo random number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A pseudo random literal value with at most `max_literal_bits` significant bits
+    fn literal(&mut self) -> u128 {
+        let bits = self.below(self.max_literal_bits as usize + 1) as u32;
+        let val = (self.next_u64() as u128) << 64 | self.next_u64() as u128;
+        val.checked_shr(u128::BITS - bits).unwrap_or(0)
+    }
+
+    /// Generate a random packet tree nested at most `depth` levels deep
+    fn packet_at(&mut self, depth: usize) -> Packet {
+        let version = self.below(8) as u8;
+        if depth >= self.max_depth || self.below(3) == 0 {
+            return Packet::new_literal_packet(version, self.literal());
+        }
+
+        let op = OPERANDS[self.below(OPERANDS.len())];
+        let num_childs = match op.is_comparison() && self.strict_arity {
+            true => 2,
+            false => self.below(self.max_childs + 1),
+        };
+        let childs = (0..num_childs).map(|_| self.packet_at(depth + 1)).collect();
+        Packet::new_op_packet(version, op, childs)
+    }
+
+    /// Generate a random chain of operator packets nested exactly `depth` levels deep,
+    /// each holding the next one and a few random literals.
+    /// Built without recursion, so `depth` is not bounded by the call stack
+    pub fn chain(&mut self, depth: usize) -> Packet {
+        let mut ret = Packet::new_literal_packet(self.below(8) as u8, self.literal());
+        for _ in 0..depth {
+            let version = self.below(8) as u8;
+            let op = OPERANDS[self.below(OPERANDS.len())];
+            let num_childs = match op.is_comparison() && self.strict_arity {
+                true => 2,
+                false => self.below(self.max_childs) + 1,
+            };
+            let mut childs: Vec<Packet> = (1..num_childs)
+                .map(|_| Packet::new_literal_packet(self.below(8) as u8, self.literal()))
+                .collect();
+            childs.insert(self.below(num_childs), ret);
+            ret = Packet::new_op_packet(version, op, childs);
+        }
+        ret
+    }
+
+    /// Generate a random packet tree
+    pub fn packet(&mut self) -> Packet {
+        self.packet_at(0)
+    }
+
+    /// A random length typ
+    pub fn length_typ(&mut self) -> LengthTyp {
+        match self.below(2) {
+            0 => LengthTyp::LengthInBits,
+            _ => LengthTyp::NumSubPackets,
+        }
+    }
+
+    /// Generate a random packet tree and encode it,
+    /// choosing a random length typ for every operator packet
+    pub fn transmission(&mut self) -> Result<(Packet, BitStream), EncodingError> {
+        let packet = self.packet();
+        let bits = encode_packet_with(&packet, |_| self.length_typ())?;
+        Ok((packet, bits))
+    }
+
+    /// Corrupt `bits` by flipping, dropping or appending random bits
+    pub fn corrupt(&mut self, bits: &BitStream) -> BitStream {
+        let mut ret = BitStream::empty();
+        ret.backing.extend_from_bitslice(&bits.backing);
+        let len = ret.backing.len().max(1);
+        match self.below(4) {
+            0 => {
+                for _ in 0..=self.below(4) {
+                    let idx = self.below(len);
+                    if let Some(mut bit) = ret.backing.get_mut(idx) {
+                        *bit = !*bit;
+                    }
+                }
+            }
+            1 => ret.backing.truncate(self.below(len)),
+            2 => {
+                for _ in 0..=self.below(32) {
+                    let bit = self.below(2) == 1;
+                    ret.backing.push(bit);
+                }
+            }
+            _ => {
+                let start = self.below(len);
+                let end = (start + self.below(16)).min(ret.backing.len());
+                for idx in start..end {
+                    let bit = self.below(2) == 1;
+                    ret.backing.set(idx, bit);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Generate `len` random bytes
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        apply_ops, apply_ops_big, encode_packet, parse_bit_stream, parse_bit_stream_all,
+        try_apply_ops, Decoder, EvalError, PacketReader, ParsingError,
+    };
+    use num_bigint::BigUint;
+
+    /// The number of random cases per property
+    const CASES: u64 = 1000;
+
+    /// The number of random cases per property for deeply nested trees
+    const DEEP_CASES: u64 = 4;
+
+    /// The nesting depth of deeply nested trees, far deeper than the call stack allows
+    const DEEP_DEPTH: usize = 10_000;
+
+    /// Evaluate and render `packet` in every way available, which must never panic
+    fn exercise(packet: &Packet) {
+        apply_ops(packet);
+        let checked = try_apply_ops(packet);
+        let big = apply_ops_big(packet);
+        match (checked, big) {
+            (Ok(x), big) => assert_eq!(Ok(BigUint::from(x)), big),
+            // Only intermediate results may overflow, the final result can be small again
+            (Err(e), Ok(_)) => assert_eq!(EvalError::Overflow, e),
+            (Err(_), Err(_)) => {}
+        }
+        let _ = packet.to_string();
+        let _ = encode_packet(packet, LengthTyp::NumSubPackets);
+    }
+
+    #[test]
+    fn test_round_trip_random_packets() {
+        for seed in 0..CASES {
+            let mut gen = PacketGenerator::new(seed);
+            let (packet, bits) = gen.transmission().unwrap();
+            let hex = bits.to_hex();
+            let all_zero = bits.backing.not_any();
+
+            let decoded = Decoder::strict().decode(bits).unwrap();
+            assert_eq!(packet, decoded, "seed {}", seed);
+            exercise(&decoded);
+
+            let mut reader = PacketReader::hex(hex.as_bytes());
+            let all = parse_bit_stream_all(hex.as_str().try_into().unwrap());
+            if all_zero {
+                // An empty `sum` packet of version 0 is all zero bits, which reads as padding
+                assert!(reader.next().is_none(), "seed {}", seed);
+                assert!(
+                    matches!(all, Err(ParsingError::EmptyPacketStream)),
+                    "seed {}",
+                    seed
+                );
+            } else {
+                assert_eq!(packet, reader.next().unwrap().unwrap(), "seed {}", seed);
+                assert!(reader.next().is_none(), "seed {}", seed);
+                let all = all.unwrap();
+                assert_eq!(1, all.len(), "seed {}", seed);
+                assert_eq!(packet, all[0], "seed {}", seed);
+            }
+
+            assert_eq!(packet, packet.to_string().parse().unwrap(), "seed {}", seed);
+        }
+    }
+
+    /// Decode `corrupted` in every way available, which must never panic
+    fn decode_corrupted(corrupted: BitStream) {
+        let hex = corrupted.to_hex();
+        if let Some(packet) = Decoder::new().decode_lenient(corrupted.clone()).packet {
+            exercise(&packet);
+        }
+        if let Ok(packet) = parse_bit_stream(corrupted) {
+            exercise(&packet);
+        }
+        for packet in PacketReader::hex(hex.as_bytes()).flatten() {
+            exercise(&packet);
+        }
+    }
+
+    /// Decode `bytes` as one or more packets in every way available, which must never panic
+    fn decode_bytes(bytes: &[u8]) {
+        if let Ok(packets) = Decoder::new().decode_all(BitStream::from(bytes)) {
+            packets.iter().for_each(exercise);
+        }
+        for packet in PacketReader::binary(bytes).flatten() {
+            exercise(&packet);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_streams_never_panic() {
+        for seed in 0..CASES {
+            let mut gen = PacketGenerator::new(seed);
+            let (_, bits) = gen.transmission().unwrap();
+            decode_corrupted(gen.corrupt(&bits));
+        }
+        for seed in 0..DEEP_CASES {
+            let mut gen = PacketGenerator::new(seed);
+            // Small literals keep `apply_ops_big` from multiplying huge numbers
+            gen.max_literal_bits = 4;
+            let packet = gen.chain(DEEP_DEPTH);
+            let bits = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+            decode_corrupted(gen.corrupt(&bits));
+        }
+    }
+
+    #[test]
+    fn test_random_bytes_never_panic() {
+        for seed in 0..CASES {
+            let mut gen = PacketGenerator::new(seed);
+            let len = gen.below(64);
+            decode_bytes(&gen.bytes(len));
+        }
+        for seed in 0..DEEP_CASES {
+            // Headers of operator packets counting a single sub packet, nested in each other
+            let mut gen = PacketGenerator::new(seed);
+            let mut bits = BitStream::empty();
+            for _ in 0..DEEP_DEPTH {
+                bits.emit(gen.below(8) as u128, 3);
+                bits.emit(u8::from(&OPERANDS[gen.below(4)]).into(), 3);
+                bits.emit(1, 1);
+                bits.emit(1, 11);
+            }
+            let mut bytes = bits.backing.into_vec();
+            let len = gen.below(64);
+            bytes.extend(gen.bytes(len));
+            decode_bytes(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_any_arity_never_panics() {
+        for seed in 0..CASES {
+            let mut gen = PacketGenerator::new(seed);
+            gen.strict_arity = false;
+            exercise(&gen.packet());
+        }
+    }
+}
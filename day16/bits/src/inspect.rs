@@ -0,0 +1,286 @@
+//! Tracing every field the decoder consumes from a stream of bits
+//!
+//! An inspection annotates the bit string of a transmission with the fields
+//! it was decoded into, to find out why a hand-built transmission is rejected.
+
+use crate::recover::DecodeState;
+use crate::{
+    convert, parse_bits_intern, BitSource, BitStream, Decoder, Packet, ParsingError, BITS_CONT,
+};
+use std::fmt;
+use std::ops::{BitXor, Shl};
+
+/// The number of bits shown in each row of the annotated bit string
+const ROW_BITS: usize = 64;
+
+/// A field of a packet consumed by the decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The `version` header
+    Version,
+
+    /// The `type` header
+    Typ,
+
+    /// The `length type` of an operator packet
+    LengthTyp,
+
+    /// The total length in bits of the sub packets
+    SubPacketLength,
+
+    /// The number of sub packets
+    SubPacketCount,
+
+    /// The bit telling whether another group of a literal value follows
+    Continue,
+
+    /// A group of bits of a literal value
+    LiteralGroup,
+}
+
+impl Field {
+    /// The char marking the bits of this field in the annotated bit string
+    fn marker(&self) -> char {
+        match self {
+            Field::Version => 'V',
+            Field::Typ => 'T',
+            Field::LengthTyp => 'I',
+            Field::SubPacketLength => 'L',
+            Field::SubPacketCount => 'N',
+            Field::Continue => 'C',
+            Field::LiteralGroup => 'G',
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    /// Prints a human readable name of this field
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Field::Version => "version",
+            Field::Typ => "type",
+            Field::LengthTyp => "length type",
+            Field::SubPacketLength => "sub packet length",
+            Field::SubPacketCount => "sub packet count",
+            Field::Continue => "continue",
+            Field::LiteralGroup => "literal group",
+        })
+    }
+}
+
+/// A field consumed by the decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldTrace {
+    /// The kind of field
+    pub field: Field,
+
+    /// The bit position of the first bit of the field
+    pub offset: usize,
+
+    /// The number of bits of the field
+    pub len: usize,
+}
+
+/// Records the fields consumed from a stream of bits
+struct Tracer<'a> {
+    /// The stream the fields are consumed from
+    stream: &'a mut BitStream,
+
+    /// The fields consumed so far
+    fields: Vec<FieldTrace>,
+}
+
+impl BitSource for Tracer<'_> {
+    fn position(&self) -> usize {
+        self.stream.position()
+    }
+
+    fn consume<T: From<bool> + BitXor<Output = T> + Shl<Output = T>>(
+        &mut self,
+        bits: usize,
+    ) -> Result<T, ParsingError> {
+        self.stream.consume(bits)
+    }
+
+    fn trace(&mut self, field: Field, offset: usize, bits: usize) {
+        self.fields.push(FieldTrace {
+            field,
+            offset,
+            len: bits,
+        });
+    }
+}
+
+/// The fields a transmission was decoded into, and the result of decoding it
+pub struct Inspection {
+    /// The inspected transmission
+    bits: BitStream,
+
+    /// The fields consumed by the decoder, in the order they were consumed
+    pub fields: Vec<FieldTrace>,
+
+    /// The decoded packet tree, or the reason the transmission was rejected
+    pub result: Result<Packet, ParsingError>,
+}
+
+impl Decoder {
+    /// Decode the first packet of `tracer`, like `decode`
+    fn decode_traced(&self, tracer: &mut Tracer) -> Result<Packet, ParsingError> {
+        let mut ret = Vec::new();
+        parse_bits_intern(tracer, &mut ret, self, &mut DecodeState::strict())?;
+        self.check_padding(tracer.stream)?;
+        ret.pop().ok_or(ParsingError::EmptyPacketStream)
+    }
+
+    /// Decode the first packet of a stream of bits like `decode`,
+    /// recording every field consumed on the way
+    pub fn inspect(&self, mut bits: BitStream) -> Inspection {
+        let mut tracer = Tracer {
+            stream: &mut bits,
+            fields: Vec::new(),
+        };
+        let result = self.decode_traced(&mut tracer);
+        let fields = tracer.fields;
+        Inspection {
+            bits,
+            fields,
+            result,
+        }
+    }
+}
+
+impl Inspection {
+    /// The value of the bits of `field`
+    pub fn value(&self, field: &FieldTrace) -> u128 {
+        convert(&self.bits[field.offset..field.offset + field.len])
+            .expect("a field is at most 15 bits wide")
+    }
+}
+
+impl fmt::Display for Inspection {
+    /// Prints the bit string with the bits of each field marked below it,
+    /// followed by a table of all fields and the result of decoding
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.bits.backing.len();
+        let mut markers = vec![' '; len];
+        for field in &self.fields {
+            markers[field.offset..field.offset + field.len].fill(field.field.marker());
+        }
+        for start in (0..len).step_by(ROW_BITS) {
+            let end = (start + ROW_BITS).min(len);
+            let bits: String = self.bits[start..end]
+                .iter()
+                .map(|bit| if *bit { '1' } else { '0' })
+                .collect();
+            let marks: String = markers[start..end].iter().collect();
+            writeln!(f, "{:>6} {}", start, bits)?;
+            writeln!(f, "{:>6} {}", "", marks.trim_end())?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:>6} {:>4}  {:<18} value", "offset", "len", "field")?;
+        for field in &self.fields {
+            let value = self.value(field);
+            // A cleared continue bit ends a literal value
+            let note = match field.field {
+                Field::Continue if field.len == BITS_CONT && value == 0 => " (last)",
+                _ => "",
+            };
+            writeln!(
+                f,
+                "{:>6} {:>4}  {:<18} {}{}",
+                field.offset, field.len, field.field, value, note
+            )?;
+        }
+
+        writeln!(f)?;
+        match &self.result {
+            Ok(packet) => write!(f, "decoded {}", packet),
+            Err(e) => {
+                write!(f, "rejected: {:?}", e)?;
+                if let Some(pos) = e.position() {
+                    write!(f, "\n{}", self.bits.hex_dump_pointer(pos))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_inspect_literal() {
+        let inspection = Decoder::new().inspect("D2FE28".try_into().unwrap());
+        let fields: Vec<_> = inspection
+            .fields
+            .iter()
+            .map(|field| (field.field, field.offset, inspection.value(field)))
+            .collect();
+        assert_eq!(
+            vec![
+                (Field::Version, 0, 6),
+                (Field::Typ, 3, 4),
+                (Field::Continue, 6, 1),
+                (Field::LiteralGroup, 7, 7),
+                (Field::Continue, 11, 1),
+                (Field::LiteralGroup, 12, 14),
+                (Field::Continue, 16, 0),
+                (Field::LiteralGroup, 17, 5),
+            ],
+            fields
+        );
+        assert_eq!(
+            "     0 110100101111111000101000
+       VVVTTTCGGGGCGGGGCGGGG
+
+offset  len  field              value
+     0    3  version            6
+     3    3  type               4
+     6    1  continue           1
+     7    4  literal group      7
+    11    1  continue           1
+    12    4  literal group      14
+    16    1  continue           0 (last)
+    17    4  literal group      5
+
+decoded (lit@6 2021)",
+            inspection.to_string()
+        );
+    }
+
+    #[test]
+    fn test_inspect_operator() {
+        let inspection = Decoder::new().inspect("38006F45291200".try_into().unwrap());
+        let fields: Vec<_> = inspection.fields.iter().map(|field| field.field).collect();
+        assert_eq!(
+            &[
+                Field::Version,
+                Field::Typ,
+                Field::LengthTyp,
+                Field::SubPacketLength,
+                Field::Version,
+                Field::Typ,
+                Field::Continue,
+                Field::LiteralGroup,
+            ],
+            &fields[..8]
+        );
+        assert!(inspection.result.is_ok());
+
+        let inspection = Decoder::new().inspect("EE00D40C823060".try_into().unwrap());
+        assert_eq!(Field::SubPacketCount, inspection.fields[3].field);
+        assert_eq!(3, inspection.value(&inspection.fields[3]));
+    }
+
+    #[test]
+    fn test_inspect_rejected() {
+        let inspection = Decoder::new().inspect("38006F452912".try_into().unwrap());
+        assert!(matches!(inspection.result, Err(ParsingError::OOB(45))));
+        let rendered = inspection.to_string();
+        assert!(rendered.contains("rejected: OOB(45)"));
+        assert!(rendered.ends_with("^ bit 45 (bit 1 of digit 11)"));
+    }
+}
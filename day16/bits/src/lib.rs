@@ -0,0 +1,1233 @@
+//! A `BITS` packet parser
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+
+use bitvec::prelude::Msb0;
+use bitvec::prelude::*;
+use bitvec::slice::BitSlice;
+use recover::DecodeState;
+use std::num::TryFromIntError;
+use std::ops::{BitXor, Index, Range, Shl};
+
+mod encode;
+mod eval;
+mod export;
+mod expr;
+mod formats;
+mod fuzz;
+mod inspect;
+mod limits;
+mod literal;
+mod optimize;
+mod recover;
+mod registry;
+mod stream;
+mod visit;
+mod vm;
+
+pub use encode::{
+    encode_hex, encode_packet, encode_packet_with, encoded_len, EncodingError, LengthTyp,
+};
+pub use eval::{apply_ops, apply_ops_big, try_apply_ops, EvalError};
+pub use export::{to_dot, to_json};
+pub use expr::{parse_expr, to_expr, ExprError};
+pub use fuzz::PacketGenerator;
+pub use inspect::{Field, FieldTrace, Inspection};
+pub use limits::DecoderLimits;
+pub use literal::LiteralValue;
+pub use optimize::{optimize, optimize_with_stats, OptimizeStats};
+pub use recover::{Diagnostic, PartialDecode};
+pub use registry::{Arity, CustomOperator, EvalFn, OperatorRegistry, RegistryError};
+pub use stream::{InputFormat, PacketReader};
+pub use visit::{
+    depth_stats, fold, node_count, version_sum, walk, DepthStats, NodeCount, PacketFold,
+    PacketVisitor,
+};
+pub use vm::{Instruction, Program};
+
+/// The `type` field of a `literal` packet
+const LITERAL_PACKET_TYP: u8 = 4;
+
+/// The `type` field of a `sum` packet
+const SUM_PACKET_TYP: u8 = 0;
+
+/// The `type` field of a `product` packet
+const PRODUCT_PACKET_TYPE: u8 = 1;
+
+/// The `type` field of a `minimum` packet
+const MINIMUM_PACKET_TYPE: u8 = 2;
+
+/// The `type` field of a `maximum` packet
+const MAXIMUM_PACKET_TYPE: u8 = 3;
+
+/// The `type` field of a `greater than` packet
+const GREATER_THAN_PACKET_TYPE: u8 = 5;
+
+/// The `type` field of a `less than` packet
+const LESS_THAN_PACKET_TYPE: u8 = 6;
+
+/// The `type` field of a `equals` packet
+const EQ_PACKET_TYPE: u8 = 7;
+
+/// The `length type` bit for a packet describing it's sub packets in length of bits
+const LENGTH_TYP_LENGTH_IN_BITS: u8 = 0;
+
+/// The `length type` bit for a packet describing it's sub packets in number of packets
+const LENGTH_TYP_NUM_SUB_PACKETS: u8 = 1;
+
+/// The length in bits for the `version` field
+const BITS_VERSION: usize = 3;
+
+/// The length in bits for the `typ` field
+const BITS_TYP: usize = 3;
+
+/// The length in bits for the `len typ` field
+const BITS_LEN_TYP: usize = 1;
+
+/// The length in bits for the `continue bit` field
+const BITS_CONT: usize = 1;
+
+/// The length in bits for the `literal value` field
+const BITS_LITERAL_VAL: usize = 4;
+
+/// The length in bits for the `sub packet length` field
+const BITS_SUB_PACKET_LENGTH: usize = 15;
+
+/// The length in bits for the `sub packet count` field
+const BITS_SUB_PACKET_COUNT: usize = 11;
+
+/// A stream of bits, backed by a vector of bytes
+#[derive(Clone)]
+pub struct BitStream {
+    /// The backing vector of bytes
+    backing: BitVec<Msb0, u8>,
+
+    /// The current index into `backing`
+    /// Get's increased by calling `consume`
+    idx: usize,
+}
+
+/// Errors that can occur while parsing the packets
+#[derive(Debug, Clone)]
+pub enum ParsingError {
+    /// Failing to read more bits then avaiable at the given bit position
+    OOB(usize),
+
+    /// Failing to parse bits into a too-small integer type
+    Overflow,
+
+    /// Failing to convert a char that is not hex into a integer
+    InvalidHexDigit(char),
+
+    /// Failing to convert a char that is not base64 into a integer
+    InvalidBase64Char(char),
+
+    /// Failing to safely cast a integer type
+    FromIntError(TryFromIntError),
+
+    /// The input hex string ended earlier than expected
+    EOF,
+
+    /// The packet stream is empty
+    EmptyPacketStream,
+
+    /// The input has not a valid length
+    InvalidInputLen,
+
+    /// Parsing encountered an invalid operand at the given bit position
+    InvalidOperand(u8, usize),
+
+    /// Parsing encountered an invalid length typ at the given bit position
+    InvalidLengthTyp(u8, usize),
+
+    /// Reading the input failed
+    Io(std::io::ErrorKind),
+
+    /// The bits after the last packet are not all zero
+    NonZeroPadding,
+
+    /// The zero padding after the last packet is longer than a byte
+    PaddingTooLong(usize),
+
+    /// An operation got a number of sub packets it does not accept
+    InvalidOperandCount(Operand, usize),
+
+    /// A literal is made of more than the given maximum number of groups.
+    /// Holds the bit position of the `literal` packet
+    LiteralTooLong(usize, usize),
+
+    /// A packet is nested deeper than the given maximum depth.
+    /// Holds the bit position of the packet
+    DepthLimitExceeded(usize, usize),
+
+    /// The transmission holds more than the given maximum number of packets.
+    /// Holds the bit position of the first packet past the limit
+    PacketLimitExceeded(usize, usize),
+
+    /// Decoding read past the given maximum number of bits
+    InputTooLong(usize),
+}
+
+impl ParsingError {
+    /// The bit position in the stream this error occurred at, if known
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Self::OOB(pos)
+            | Self::InvalidOperand(_, pos)
+            | Self::InvalidLengthTyp(_, pos)
+            | Self::LiteralTooLong(_, pos)
+            | Self::DepthLimitExceeded(_, pos)
+            | Self::PacketLimitExceeded(_, pos)
+            | Self::InputTooLong(pos) => Some(*pos),
+            _ => None,
+        }
+    }
+}
+
+impl From<TryFromIntError> for ParsingError {
+    fn from(e: TryFromIntError) -> Self {
+        Self::FromIntError(e)
+    }
+}
+
+impl From<std::io::Error> for ParsingError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.kind())
+    }
+}
+
+/// Tries to convert two hex chars into a byte
+fn byte_from_hex_chars(chars: (char, char)) -> Result<u8, ParsingError> {
+    let mut val: u8 = 0;
+    for c in [chars.0, chars.1] {
+        let byte: u8 = c
+            .to_digit(16)
+            .ok_or(ParsingError::InvalidHexDigit(c))?
+            .try_into()?;
+        val = (val << 4) | byte;
+    }
+    Ok(val)
+}
+
+impl TryFrom<&str> for BitStream {
+    type Error = ParsingError;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        if !s.len().is_multiple_of(2) {
+            return Err(ParsingError::InvalidInputLen);
+        }
+        s.chars()
+            .step_by(2)
+            .zip(s.chars().skip(1).step_by(2))
+            .map(byte_from_hex_chars)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|bytes| Self::from(&bytes[..]))
+    }
+}
+
+impl Index<Range<usize>> for BitStream {
+    type Output = BitSlice<Msb0, u8>;
+
+    fn index(&self, range: Range<usize>) -> &Self::Output {
+        &self.backing[range]
+    }
+}
+
+impl BitStream {
+    /// Construct a new stream without any bits
+    fn empty() -> Self {
+        Self {
+            backing: BitVec::new(),
+            idx: 0,
+        }
+    }
+
+    /// The bits that have not been consumed yet
+    fn remaining(&self) -> &BitSlice<Msb0, u8> {
+        &self.backing[self.idx..]
+    }
+
+    /// Render the hex digits around bit position `pos` with a pointer
+    /// to the digit containing that bit below them
+    pub fn hex_dump_pointer(&self, pos: usize) -> String {
+        let hex = self.to_hex();
+        let nibble = pos / 4;
+        // Positions past the end point right behind the last digit
+        let shown = nibble.min(hex.len());
+        let start = shown.saturating_sub(HEX_DUMP_CONTEXT);
+        let end = (shown + HEX_DUMP_CONTEXT + 1).min(hex.len());
+        let prefix = if start > 0 { "..." } else { "" };
+        let suffix = if end < hex.len() { "..." } else { "" };
+        let pointer_col = prefix.len() + shown - start;
+        format!(
+            "{}{}{}\n{:pointer_col$}^ bit {} (bit {} of digit {})",
+            prefix,
+            &hex[start..end],
+            suffix,
+            "",
+            pos,
+            pos % 4,
+            nibble,
+        )
+    }
+}
+
+/// The number of hex digits shown on each side of the pointer in `hex_dump_pointer`
+const HEX_DUMP_CONTEXT: usize = 32;
+
+/// A source of bits the packet parser can consume from
+pub(crate) trait BitSource {
+    /// The absolute bit position of the next bit to consume
+    fn position(&self) -> usize;
+
+    /// Consume `bits` number of bits from this source and try to convert it into an integer
+    fn consume<T: From<bool> + BitXor<Output = T> + Shl<Output = T>>(
+        &mut self,
+        bits: usize,
+    ) -> Result<T, ParsingError>;
+
+    /// Called after the `bits` bits of `field` starting at `offset` have been consumed
+    fn trace(&mut self, _field: Field, _offset: usize, _bits: usize) {}
+}
+
+impl BitSource for BitStream {
+    fn position(&self) -> usize {
+        self.idx
+    }
+
+    fn consume<T: From<bool> + BitXor<Output = T> + Shl<Output = T>>(
+        &mut self,
+        bits: usize,
+    ) -> Result<T, ParsingError> {
+        if self.idx + bits > self.backing.len() {
+            return Err(ParsingError::OOB(self.idx));
+        }
+        let ret = convert(&self[self.idx..self.idx + bits]);
+        self.idx += bits;
+        ret
+    }
+}
+
+/// Try to convert a set of bits into a integer
+fn convert<T: From<bool> + BitXor<Output = T> + Shl<Output = T>>(
+    bits: &BitSlice<Msb0, u8>,
+) -> Result<T, ParsingError> {
+    let have_space = std::mem::size_of::<T>() * 8;
+    if bits.len() > have_space {
+        return Err(ParsingError::Overflow);
+    }
+
+    let ret = bits.iter().fold(T::from(false), |result, bit| {
+        (result << T::from(true)) ^ T::from(*bit)
+    });
+    Ok(ret)
+}
+
+/// A packet in the `BITS` system, holding literal values of type `L`
+#[derive(Debug)]
+pub struct Packet<L = u128> {
+    /// The version header of this packet
+    version: u8,
+
+    /// The pay load of this packet.
+    /// Either a literal value of an op on a list of sub packets
+    payload: Payload<L>,
+
+    /// The bits this packet was decoded from.
+    /// `None` if the packet was not decoded from a stream
+    span: Option<Span>,
+}
+
+/// A range of bits in a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The bit position of the first bit
+    pub offset: usize,
+
+    /// The number of bits
+    pub len: usize,
+}
+
+impl<L> Drop for Packet<L> {
+    /// Drops the sub packets iteratively, so deeply nested trees do not overflow the stack
+    fn drop(&mut self) {
+        if let Payload::Op(_, childs) = &mut self.payload {
+            let mut stack = std::mem::take(childs);
+            while let Some(mut packet) = stack.pop() {
+                if let Payload::Op(_, childs) = &mut packet.payload {
+                    stack.append(childs);
+                }
+            }
+        }
+    }
+}
+
+impl<L: PartialEq> PartialEq for Packet<L> {
+    /// Two packets are equal if their versions and payloads are equal,
+    /// regardless of where they were decoded from.
+    /// Compares the sub packets iteratively, so deeply nested trees do not overflow the stack
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some((left, right)) = stack.pop() {
+            if left.version != right.version {
+                return false;
+            }
+            match (&left.payload, &right.payload) {
+                (Payload::Literal(left), Payload::Literal(right)) if left == right => {}
+                (Payload::Op(left_op, left), Payload::Op(right_op, right))
+                    if left_op == right_op && left.len() == right.len() =>
+                {
+                    stack.extend(left.iter().zip(right));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// The payload of a `BITS` packet.
+/// Can either be a literal value or an operation on a set of packets
+#[derive(Debug, PartialEq)]
+pub enum Payload<L = u128> {
+    /// Payload that simpy hols a literal value
+    Literal(L),
+
+    /// A operation describing how to alter the child packets
+    Op(Operand, Vec<Packet<L>>),
+}
+
+/// A operation that has to be applied on a set of packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// Calculate the sum of the set of packets
+    Sum,
+
+    /// Calculate the product of the set of packets
+    Product,
+
+    /// Calculate the minimum of the set of packets
+    Minimum,
+
+    /// Calculate the maximum of the set of packets
+    Maximum,
+
+    /// Calculate whether the first packet is greater than the second packet
+    /// This operation always operates on exactly two packets
+    GreaterThan,
+
+    /// Calculate whether the first packet is less than the second packet
+    /// This operation always operates on exactly two packets
+    LessThan,
+
+    /// Calculate whether the first packet is equal to the second packet
+    /// This operation always operates on exactly two packets
+    Equals,
+
+    /// A custom operation registered in an `OperatorRegistry` for the given type id
+    Custom(u8),
+}
+
+impl TryFrom<u8> for Operand {
+    /// The type id that does not describe an operation
+    type Error = u8;
+
+    fn try_from(x: u8) -> Result<Self, Self::Error> {
+        Ok(match x {
+            SUM_PACKET_TYP => Operand::Sum,
+            PRODUCT_PACKET_TYPE => Operand::Product,
+            MINIMUM_PACKET_TYPE => Operand::Minimum,
+            MAXIMUM_PACKET_TYPE => Operand::Maximum,
+            GREATER_THAN_PACKET_TYPE => Operand::GreaterThan,
+            LESS_THAN_PACKET_TYPE => Operand::LessThan,
+            EQ_PACKET_TYPE => Operand::Equals,
+            _ => return Err(x),
+        })
+    }
+}
+
+impl Operand {
+    /// Returns whether this operation always operates on exactly two packets
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operand::GreaterThan | Operand::LessThan | Operand::Equals
+        )
+    }
+}
+
+impl<L> Packet<L> {
+    /// Construct a new `literal` packet
+    pub fn new_literal_packet(version: u8, val: L) -> Self {
+        Self {
+            version,
+            payload: Payload::Literal(val),
+            span: None,
+        }
+    }
+
+    /// Construct a new `operation` packet with child packets
+    pub fn new_op_packet(version: u8, op: Operand, childs: Vec<Packet<L>>) -> Self {
+        Self {
+            version,
+            payload: Payload::Op(op, childs),
+            span: None,
+        }
+    }
+
+    /// Attach the bits this packet was decoded from
+    fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// The version header of this packet
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The pay load of this packet
+    pub fn payload(&self) -> &Payload<L> {
+        &self.payload
+    }
+
+    /// The sub packets of this packet, empty for a `literal` packet
+    pub fn childs(&self) -> &[Packet<L>] {
+        match &self.payload {
+            Payload::Literal(_) => &[],
+            Payload::Op(_, childs) => childs,
+        }
+    }
+
+    /// The bits this packet was decoded from, if it was decoded from a stream
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+/// Consumes `n` bits of the field `field` from the stream `bin` and increases `acc` by `n`
+macro_rules! cat {
+    ($acc: ident, $bin: ident, $n: ident, $field: expr) => {{
+        let offset = $bin.position();
+        let x = $bin.consume($n)?;
+        $bin.trace($field, offset, $n);
+        $acc += $n;
+        x
+    }};
+}
+
+/// The maximum number of padding bits after the last packet in strict mode
+const MAX_PADDING_BITS: usize = 7;
+
+/// The number of bits of the shortest packet, a `literal` of a single group
+pub(crate) const MIN_PACKET_BITS: usize = BITS_VERSION + BITS_TYP + BITS_CONT + BITS_LITERAL_VAL;
+
+/// Options controlling how a stream of bits is decoded into packets
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    /// Reject streams whose bits after the last packet are not
+    /// zero padding shorter than a byte
+    pub strict_padding: bool,
+
+    /// The operations the `type` field of operator packets is decoded as
+    pub registry: OperatorRegistry,
+
+    /// Reject literals made of more than this number of groups.
+    /// A literal always has at least one group
+    pub max_literal_groups: Option<usize>,
+
+    /// Limits on the nesting depth, number of packets and number of bits decoded
+    pub limits: DecoderLimits,
+}
+
+impl Decoder {
+    /// Construct a decoder that ignores any bits after the last packet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a decoder that rejects anything but zero padding after the last packet
+    pub fn strict() -> Self {
+        Self {
+            strict_padding: true,
+            ..Self::default()
+        }
+    }
+
+    /// Parse the first packet of a stream of bits into a `BITS` packet tree
+    pub fn decode(&self, stream: BitStream) -> Result<Packet, ParsingError> {
+        self.decode_as(stream)
+    }
+
+    /// Parse the first packet of a stream of bits into a `BITS` packet tree
+    /// holding literal values of type `L`.
+    /// Fails with `ParsingError::Overflow` if a literal does not fit into `L`
+    pub fn decode_as<L: LiteralValue>(
+        &self,
+        mut stream: BitStream,
+    ) -> Result<Packet<L>, ParsingError> {
+        let mut ret = Vec::new();
+        parse_bits_intern(&mut stream, &mut ret, self, &mut DecodeState::strict())?;
+        self.check_padding(&stream)?;
+        ret.pop().ok_or(ParsingError::EmptyPacketStream)
+    }
+
+    /// Parse a stream of bits into a sequence of top-level `BITS` packet trees.
+    /// Decoding stops as soon as only zero bits, or too few bits to hold a packet, are left.
+    /// A packet made of only zero bits, an empty `sum` packet of version `0`
+    /// giving the length of it's sub packets in bits, can not be told apart from padding,
+    /// so it is never decoded. Use `decode` to decode such a packet on it's own
+    pub fn decode_all(&self, mut stream: BitStream) -> Result<Vec<Packet>, ParsingError> {
+        let mut ret = Vec::new();
+        let mut state = DecodeState::strict();
+        while stream.remaining().any() && stream.remaining().len() >= MIN_PACKET_BITS {
+            parse_bits_intern(&mut stream, &mut ret, self, &mut state)?;
+        }
+        if ret.is_empty() {
+            return Err(ParsingError::EmptyPacketStream);
+        }
+        self.check_padding(&stream)?;
+        Ok(ret)
+    }
+
+    /// In strict mode, check that only zero padding shorter than a byte is left in `stream`
+    fn check_padding(&self, stream: &BitStream) -> Result<(), ParsingError> {
+        if !self.strict_padding {
+            return Ok(());
+        }
+        let rest = stream.remaining();
+        if rest.any() {
+            return Err(ParsingError::NonZeroPadding);
+        }
+        if rest.len() > MAX_PADDING_BITS {
+            return Err(ParsingError::PaddingTooLong(rest.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Parse a stream of bits into a `BITS` packet tree.
+/// Any bits after the first packet are ignored
+pub fn parse_bit_stream(stream: BitStream) -> Result<Packet, ParsingError> {
+    Decoder::new().decode(stream)
+}
+
+/// Parse a stream of bits into a sequence of top-level `BITS` packet trees.
+/// Any zero bits, or bits too few to hold a packet, after the last packet are ignored
+pub fn parse_bit_stream_all(stream: BitStream) -> Result<Vec<Packet>, ParsingError> {
+    Decoder::new().decode_all(stream)
+}
+
+/// How an operator packet being decoded describes it's sub packets
+enum SubPackets {
+    /// The sub packets are `len` bits long, `read` of them have been decoded
+    Bits {
+        /// The total length of the sub packets in bits
+        len: usize,
+
+        /// The number of bits of sub packets decoded so far
+        read: usize,
+    },
+
+    /// There are `count` sub packets, `found` of them have been decoded
+    Count {
+        /// The number of sub packets
+        count: usize,
+
+        /// The number of sub packets decoded so far
+        found: usize,
+    },
+}
+
+/// An operator packet whose header has been decoded, but not all of it's sub packets
+struct OpenOperator<L> {
+    /// The bit position of the packet
+    offset: usize,
+
+    /// The version header of the packet
+    version: u8,
+
+    /// The `type` field of the packet
+    typ: u8,
+
+    /// The operation the `type` field was decoded as
+    op: Operand,
+
+    /// The number of bits of the header and the sub packets decoded so far
+    read_all: usize,
+
+    /// How many sub packets are left to decode
+    sub_packets: SubPackets,
+
+    /// The sub packets decoded so far
+    childs: Vec<Packet<L>>,
+}
+
+impl<L> OpenOperator<L> {
+    /// Returns whether all sub packets have been decoded,
+    /// or the stream ended early so no further ones can be
+    fn is_complete(&self, state: &DecodeState) -> bool {
+        let done = match self.sub_packets {
+            SubPackets::Bits { len, read } => read >= len,
+            SubPackets::Count { count, found } => found >= count,
+        };
+        done || state.is_truncated()
+    }
+
+    /// Add the result of decoding the sub packet at `child_offset`.
+    /// A failed sub packet truncates the stream, failing in strict mode
+    fn add_child(
+        &mut self,
+        child_offset: usize,
+        child: Result<(Packet<L>, usize), ParsingError>,
+        state: &mut DecodeState,
+    ) -> Result<(), ParsingError> {
+        let len = match child {
+            Ok((packet, len)) => {
+                self.childs.push(packet);
+                len
+            }
+            Err(e) => {
+                state.truncate(child_offset, e)?;
+                0
+            }
+        };
+        self.read_all += len;
+        match &mut self.sub_packets {
+            SubPackets::Bits { read, .. } => *read += len,
+            SubPackets::Count { found, .. } => *found += 1,
+        }
+        Ok(())
+    }
+
+    /// Build the packet once all sub packets are decoded, returning it with it's length in bits
+    fn finish<S: BitSource>(
+        self,
+        stream: &S,
+        decoder: &Decoder,
+        state: &mut DecodeState,
+    ) -> Result<(Packet<L>, usize), ParsingError> {
+        let arity_ok = match decoder.registry.custom(self.typ) {
+            Some(custom) => custom.arity.accepts(self.childs.len()),
+            None => !self.op.is_comparison() || self.childs.len() == 2,
+        };
+        // A truncated operation is missing sub packets for a reason already reported
+        if !arity_ok && !state.is_truncated() {
+            let e = ParsingError::InvalidOperandCount(self.op, self.childs.len());
+            state.report(self.offset, e)?;
+        }
+        decoder.limits.check_bits(stream.position())?;
+        let packet = Packet::new_op_packet(self.version, self.op, self.childs);
+        let span = Span {
+            offset: self.offset,
+            len: self.read_all,
+        };
+        Ok((packet.with_span(span), self.read_all))
+    }
+}
+
+/// The start of a packet: either a whole `literal` packet with it's length in bits,
+/// or an operator packet waiting for it's sub packets
+enum PacketStart<L> {
+    /// A fully decoded `literal` packet and it's length in bits
+    Literal(Packet<L>, usize),
+
+    /// An operator packet whose sub packets are decoded next
+    Operator(OpenOperator<L>),
+}
+
+/// Decode the header of the packet at the current position of `stream`,
+/// and the value if it is a `literal` packet.
+/// `depth` is the number of operator packets the packet is nested in
+fn parse_packet_start<S: BitSource, L: LiteralValue>(
+    stream: &mut S,
+    decoder: &Decoder,
+    state: &mut DecodeState,
+    depth: usize,
+) -> Result<PacketStart<L>, ParsingError> {
+    let offset = stream.position();
+    decoder.limits.check_packet(offset, depth, state.packets)?;
+    state.packets += 1;
+    let mut read_all: usize = 0;
+    let version: u8 = cat!(read_all, stream, BITS_VERSION, Field::Version);
+    let typ: u8 = cat!(read_all, stream, BITS_TYP, Field::Typ);
+
+    if typ == LITERAL_PACKET_TYP {
+        let mut cont: u8 = cat!(read_all, stream, BITS_CONT, Field::Continue);
+        let val: u8 = cat!(read_all, stream, BITS_LITERAL_VAL, Field::LiteralGroup);
+        let mut acc_local = vec![val];
+
+        while cont == 1 {
+            // A single literal can be arbitrarily long, so the bit limit is checked per group
+            decoder.limits.check_bits(stream.position())?;
+            if let Some(max) = decoder.max_literal_groups {
+                if acc_local.len() >= max {
+                    return Err(ParsingError::LiteralTooLong(max, offset));
+                }
+            }
+            cont = cat!(read_all, stream, BITS_CONT, Field::Continue);
+            acc_local.push(cat!(
+                read_all,
+                stream,
+                BITS_LITERAL_VAL,
+                Field::LiteralGroup
+            ));
+        }
+
+        let val = acc_local
+            .into_iter()
+            .try_fold(L::default(), |acc, x| acc.push_group(x));
+        let val = match val {
+            Some(val) => val,
+            None => {
+                state.report(offset, ParsingError::Overflow)?;
+                L::default()
+            }
+        };
+        decoder.limits.check_bits(stream.position())?;
+        let packet = Packet::new_literal_packet(version, val).with_span(Span {
+            offset,
+            len: read_all,
+        });
+        return Ok(PacketStart::Literal(packet, read_all));
+    }
+
+    let op = match decoder.registry.operand(typ) {
+        Some(op) => op,
+        None => {
+            let e = ParsingError::InvalidOperand(typ, offset + BITS_VERSION);
+            state.report(offset, e)?;
+            Operand::Custom(typ)
+        }
+    };
+    let len_typ_pos = stream.position();
+    let len_typ: u8 = cat!(read_all, stream, BITS_LEN_TYP, Field::LengthTyp);
+    let sub_packets = match len_typ {
+        LENGTH_TYP_LENGTH_IN_BITS => {
+            let len: u16 = cat!(
+                read_all,
+                stream,
+                BITS_SUB_PACKET_LENGTH,
+                Field::SubPacketLength
+            );
+            SubPackets::Bits {
+                len: len.into(),
+                read: 0,
+            }
+        }
+        LENGTH_TYP_NUM_SUB_PACKETS => {
+            let count: u16 = cat!(
+                read_all,
+                stream,
+                BITS_SUB_PACKET_COUNT,
+                Field::SubPacketCount
+            );
+            SubPackets::Count {
+                count: count.into(),
+                found: 0,
+            }
+        }
+        _ => return Err(ParsingError::InvalidLengthTyp(len_typ, len_typ_pos)),
+    };
+    Ok(PacketStart::Operator(OpenOperator {
+        offset,
+        version,
+        typ,
+        op,
+        read_all,
+        sub_packets,
+        childs: Vec::new(),
+    }))
+}
+
+/// Parse a stream of bits into a `BITS` packet tree, pushing it onto `acc`.
+/// Recoverable errors are passed to `state`, which decides whether decoding goes on.
+/// The operator packets being decoded are kept on a stack on the heap,
+/// so deeply nested packets do not overflow the call stack.
+/// Returns the number of bits the packet was decoded from
+fn parse_bits_intern<S: BitSource, L: LiteralValue>(
+    stream: &mut S,
+    acc: &mut Vec<Packet<L>>,
+    decoder: &Decoder,
+    state: &mut DecodeState,
+) -> Result<usize, ParsingError> {
+    let mut stack: Vec<OpenOperator<L>> = Vec::new();
+    loop {
+        let offset = stream.position();
+        // The packet just decoded, and the bit position it started at
+        let mut finished = match parse_packet_start(stream, decoder, state, stack.len()) {
+            Ok(PacketStart::Literal(packet, len)) => Some((offset, Ok((packet, len)))),
+            Ok(PacketStart::Operator(open)) => {
+                stack.push(open);
+                None
+            }
+            Err(e) => Some((offset, Err(e))),
+        };
+
+        // Hand finished packets to their parents, finishing every parent that is complete
+        loop {
+            if let Some((offset, child)) = finished.take() {
+                match stack.last_mut() {
+                    Some(parent) => parent.add_child(offset, child, state)?,
+                    None => {
+                        let (packet, len) = child?;
+                        acc.push(packet);
+                        return Ok(len);
+                    }
+                }
+            }
+            match stack.last() {
+                Some(open) if open.is_complete(state) => {
+                    let open = stack.pop().expect("checked there is an open operator");
+                    let offset = open.offset;
+                    finished = Some((offset, open.finish(stream, decoder, state)));
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The operator examples of the puzzle, with the values they evaluate to
+    pub(crate) const EXAMPLES: [(&str, u128); 8] = [
+        ("C200B40A82", 3),
+        ("04005AC33890", 54),
+        ("880086C3E88112", 7),
+        ("CE00C43D881120", 9),
+        ("D8005AC2A8F0", 1),
+        ("F600BC2D8F", 0),
+        ("9C005AC2F8F0", 0),
+        ("9C0141080250320F1802104A08", 1),
+    ];
+
+    /// Random packet trees together with their seeds,
+    /// every other one with comparisons of any arity
+    pub(crate) fn random_packets() -> impl Iterator<Item = (u64, Packet)> {
+        (0..1000).map(|seed| {
+            let mut gen = PacketGenerator::new(seed);
+            gen.strict_arity = seed % 2 == 0;
+            (seed, gen.packet())
+        })
+    }
+
+    #[test]
+    fn test_packet_stream() {
+        let s = "D2FE28";
+        let bin: BitStream = s.try_into().unwrap();
+        assert_eq!(vec![0xd2, 0xfe, 0x28], bin.backing.as_raw_slice());
+
+        let s = "EE00D40C823060";
+        let bin: BitStream = s.try_into().unwrap();
+        assert_eq!(
+            vec![0xEE, 0x00, 0xD4, 0x0C, 0x82, 0x30, 0x60],
+            bin.backing.as_raw_slice()
+        );
+        assert_eq!(238, convert(&bin[0..8]).unwrap());
+        assert_eq!(212, convert(&bin[16..24]).unwrap());
+
+        let s = "FF";
+        let bin: BitStream = s.try_into().unwrap();
+        assert_eq!(255, convert(&bin[0..8]).unwrap());
+        assert_eq!(15, convert(&bin[0..4]).unwrap());
+
+        let s = "FFFF";
+        let mut bin: BitStream = s.try_into().unwrap();
+        assert_eq!(15, bin.consume(4).unwrap());
+        assert_eq!(15, bin.consume(4).unwrap());
+        assert_eq!(255, bin.consume(8).unwrap());
+
+        let s = "D2FE28";
+        let mut bin: BitStream = s.try_into().unwrap();
+        assert_eq!(6, bin.consume(3).unwrap());
+        assert_eq!(4, bin.consume(3).unwrap());
+        assert_eq!(1, bin.consume(1).unwrap());
+        let a: u8 = bin.consume(4).unwrap();
+        assert_eq!(1, bin.consume(1).unwrap());
+        let b: u8 = bin.consume(4).unwrap();
+        assert_eq!(0, bin.consume(1).unwrap());
+        let c: u8 = bin.consume(4).unwrap();
+        let x = (a as u64) << 4 | b as u64;
+        let x = x << 4 | c as u64;
+        assert_eq!(2021, x);
+        assert_eq!(0, bin.consume(3).unwrap());
+    }
+
+    #[test]
+    fn test_parse_packets() {
+        let s = "D2FE28";
+        let bin: BitStream = s.try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!(
+            Packet {
+                version: 6,
+                span: None,
+                payload: Payload::Literal(2021)
+            },
+            packet
+        );
+
+        let s = "38006F45291200";
+        let bin: BitStream = s.try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!(
+            Packet {
+                version: 1,
+                span: None,
+                payload: Payload::Op(
+                    Operand::LessThan,
+                    vec![
+                        Packet {
+                            version: 6,
+                            span: None,
+                            payload: Payload::Literal(10)
+                        },
+                        Packet {
+                            version: 2,
+                            span: None,
+                            payload: Payload::Literal(20)
+                        }
+                    ]
+                )
+            },
+            packet
+        );
+
+        let s = "EE00D40C823060";
+        let bin: BitStream = s.try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!(
+            Packet {
+                version: 7,
+                span: None,
+                payload: Payload::Op(
+                    Operand::Maximum,
+                    vec![
+                        Packet {
+                            version: 2,
+                            span: None,
+                            payload: Payload::Literal(1)
+                        },
+                        Packet {
+                            version: 4,
+                            span: None,
+                            payload: Payload::Literal(2)
+                        },
+                        Packet {
+                            version: 1,
+                            span: None,
+                            payload: Payload::Literal(3)
+                        }
+                    ]
+                )
+            },
+            packet
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_packets() {
+        let s = "D2FE28";
+        let bin: BitStream = s.try_into().unwrap();
+        let packets = parse_bit_stream_all(bin).unwrap();
+        assert_eq!(vec![Packet::new_literal_packet(6, 2021)], packets);
+
+        let packet = |s: &str| parse_bit_stream(s.try_into().unwrap()).unwrap();
+        let mut bin = encode_packet(&packet("C200B40A82"), LengthTyp::LengthInBits).unwrap();
+        let second = encode_packet(&packet("EE00D40C823060"), LengthTyp::NumSubPackets).unwrap();
+        bin.backing.extend_from_bitslice(&second.backing);
+        let bin: BitStream = bin.to_hex().as_str().try_into().unwrap();
+        let packets = Decoder::strict().decode_all(bin).unwrap();
+        assert_eq!(
+            vec![packet("C200B40A82"), packet("EE00D40C823060")],
+            packets
+        );
+
+        let bin: BitStream = "0000".try_into().unwrap();
+        assert!(matches!(
+            parse_bit_stream_all(bin),
+            Err(ParsingError::EmptyPacketStream)
+        ));
+    }
+
+    #[test]
+    fn test_strict_padding() {
+        for s in ["D2FE28", "38006F45291200", "EE00D40C823060"] {
+            let bin: BitStream = s.try_into().unwrap();
+            assert!(Decoder::strict().decode(bin).is_ok());
+        }
+
+        let bin: BitStream = "D2FE29".try_into().unwrap();
+        assert!(parse_bit_stream(bin).is_ok());
+        let bin: BitStream = "D2FE29".try_into().unwrap();
+        assert!(matches!(
+            Decoder::strict().decode(bin),
+            Err(ParsingError::NonZeroPadding)
+        ));
+
+        let bin: BitStream = "D2FE2800".try_into().unwrap();
+        assert!(parse_bit_stream(bin).is_ok());
+        let bin: BitStream = "D2FE2800".try_into().unwrap();
+        assert!(matches!(
+            Decoder::strict().decode(bin),
+            Err(ParsingError::PaddingTooLong(11))
+        ));
+        let bin: BitStream = "D2FE2800".try_into().unwrap();
+        assert!(matches!(
+            Decoder::strict().decode_all(bin),
+            Err(ParsingError::PaddingTooLong(11))
+        ));
+    }
+
+    #[test]
+    fn test_decode_all_short_tail() {
+        // The last 3 bits are not zero, but too few to hold a packet
+        let bin: BitStream = "D2FE29".try_into().unwrap();
+        assert!(matches!(
+            Decoder::strict().decode_all(bin),
+            Err(ParsingError::NonZeroPadding)
+        ));
+        let bin: BitStream = "D2FE29".try_into().unwrap();
+        assert_eq!(
+            vec![Packet::new_literal_packet(6, 2021)],
+            parse_bit_stream_all(bin).unwrap()
+        );
+
+        // A tail long enough to hold a packet is still decoded as one
+        let bin: BitStream = "D2FE29FF".try_into().unwrap();
+        assert!(matches!(
+            parse_bit_stream_all(bin),
+            Err(ParsingError::OOB(_))
+        ));
+    }
+
+    #[test]
+    fn test_spans() {
+        let s = "38006F45291200";
+        let bin: BitStream = s.try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!(Some(Span { offset: 0, len: 49 }), packet.span());
+        let Payload::Op(_, childs) = &packet.payload else {
+            panic!("expected an operator packet");
+        };
+        assert_eq!(
+            Some(Span {
+                offset: 22,
+                len: 11
+            }),
+            childs[0].span()
+        );
+        assert_eq!(
+            Some(Span {
+                offset: 33,
+                len: 16
+            }),
+            childs[1].span()
+        );
+
+        let mut bin = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+        bin.backing.extend_from_bitslice(&bin.backing.clone());
+        let packets = parse_bit_stream_all(bin).unwrap();
+        assert_eq!(
+            Some(Span {
+                offset: 45,
+                len: 45
+            }),
+            packets[1].span()
+        );
+
+        assert_eq!(None, Packet::new_literal_packet(6, 2021).span());
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let bin: BitStream = "D2FE".try_into().unwrap();
+        let err = parse_bit_stream(bin).unwrap_err();
+        assert!(matches!(err, ParsingError::OOB(16)));
+        assert_eq!(Some(16), err.position());
+
+        let bin: BitStream = "D2FE".try_into().unwrap();
+        assert_eq!(
+            "D2FE\n    ^ bit 16 (bit 0 of digit 4)",
+            bin.hex_dump_pointer(16)
+        );
+        assert_eq!(
+            "D2FE\n  ^ bit 9 (bit 1 of digit 2)",
+            bin.hex_dump_pointer(9)
+        );
+        assert_eq!(
+            "D2FE\n    ^ bit 4000 (bit 0 of digit 1000)",
+            bin.hex_dump_pointer(4000)
+        );
+
+        let s = "0".repeat(100);
+        let bin: BitStream = s.as_str().try_into().unwrap();
+        let dump = bin.hex_dump_pointer(200);
+        let (hex, pointer) = dump.split_once('\n').unwrap();
+        assert_eq!(format!("...{}...", "0".repeat(65)), hex);
+        assert_eq!(
+            format!("{}^ bit 200 (bit 0 of digit 50)", " ".repeat(35)),
+            pointer
+        );
+    }
+
+    #[test]
+    fn test_literal_overflow() {
+        let literal = |groups: &[u8]| {
+            let mut bin = BitStream::empty();
+            bin.emit(0, BITS_VERSION);
+            bin.emit(LITERAL_PACKET_TYP.into(), BITS_TYP);
+            for (ii, group) in groups.iter().enumerate() {
+                bin.emit((ii + 1 != groups.len()).into(), BITS_CONT);
+                bin.emit((*group).into(), BITS_LITERAL_VAL);
+            }
+            parse_bit_stream(bin)
+        };
+
+        let packet = literal(&[0xF; 32]).unwrap();
+        assert_eq!(Payload::Literal(u128::MAX), packet.payload);
+
+        let mut groups = vec![0; 8];
+        groups.extend_from_slice(&[0xF; 32]);
+        let packet = literal(&groups).unwrap();
+        assert_eq!(Payload::Literal(u128::MAX), packet.payload);
+
+        assert!(matches!(literal(&[1; 33]), Err(ParsingError::Overflow)));
+    }
+
+    #[test]
+    fn test_ops() {
+        let (s, _) = EXAMPLES[0];
+        let bin: BitStream = s.try_into().unwrap();
+        let packet = parse_bit_stream(bin).unwrap();
+        assert_eq!(
+            Packet {
+                version: 6,
+                span: None,
+                payload: Payload::Op(
+                    Operand::Sum,
+                    vec![
+                        Packet {
+                            version: 6,
+                            span: None,
+                            payload: Payload::Literal(1)
+                        },
+                        Packet {
+                            version: 2,
+                            span: None,
+                            payload: Payload::Literal(2)
+                        },
+                    ]
+                )
+            },
+            packet
+        );
+        for (s, expected) in EXAMPLES {
+            let bin: BitStream = s.try_into().unwrap();
+            let packet = parse_bit_stream(bin).unwrap();
+            assert_eq!(expected, apply_ops(&packet), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Bounding the resources spent decoding a transmission
+//!
+//! Packets are decoded without recursion, so any nesting depth fits on the stack,
+//! but a transmission from an untrusted source could still make the decoder
+//! spend an unbounded amount of memory and time.
+
+use crate::ParsingError;
+
+/// The maximum nesting depth of `DecoderLimits::untrusted`
+const UNTRUSTED_MAX_DEPTH: usize = 256;
+
+/// The maximum number of packets of `DecoderLimits::untrusted`
+const UNTRUSTED_MAX_PACKETS: usize = 1 << 16;
+
+/// The maximum number of bits of `DecoderLimits::untrusted`
+const UNTRUSTED_MAX_BITS: usize = 1 << 20;
+
+/// Limits on the transmissions a decoder accepts. `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecoderLimits {
+    /// The maximum number of operator packets a packet may be nested in.
+    /// A single packet has a depth of `0`
+    pub max_depth: Option<usize>,
+
+    /// The maximum number of packets in a packet tree.
+    /// `decode_all` counts the packets of all top-level packet trees together
+    pub max_packets: Option<usize>,
+
+    /// The maximum number of bits a transmission may be decoded from.
+    /// Checked at the start and end of each packet and before each literal group,
+    /// so decoding stops at most a packet header or a literal group past the limit
+    pub max_bits: Option<usize>,
+}
+
+impl DecoderLimits {
+    /// Limits that accept any transmission
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limits suitable for decoding transmissions from untrusted sources
+    pub fn untrusted() -> Self {
+        Self {
+            max_depth: Some(UNTRUSTED_MAX_DEPTH),
+            max_packets: Some(UNTRUSTED_MAX_PACKETS),
+            max_bits: Some(UNTRUSTED_MAX_BITS),
+        }
+    }
+
+    /// Check that a packet at bit position `offset` and nesting depth `depth`
+    /// may be decoded, `packets` being the number of packets decoded before it
+    pub(crate) fn check_packet(
+        &self,
+        offset: usize,
+        depth: usize,
+        packets: usize,
+    ) -> Result<(), ParsingError> {
+        if let Some(max) = self.max_depth {
+            if depth > max {
+                return Err(ParsingError::DepthLimitExceeded(max, offset));
+            }
+        }
+        if let Some(max) = self.max_packets {
+            if packets >= max {
+                return Err(ParsingError::PacketLimitExceeded(max, offset));
+            }
+        }
+        self.check_bits(offset)
+    }
+
+    /// Check that decoding up to bit position `position` did not read past the limit
+    pub(crate) fn check_bits(&self, position: usize) -> Result<(), ParsingError> {
+        match self.max_bits {
+            Some(max) if position > max => Err(ParsingError::InputTooLong(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        depth_stats, encode_packet, parse_bit_stream, try_apply_ops, BitStream, Decoder, LengthTyp,
+        Packet, PacketReader,
+    };
+
+    /// A decoder using `limits`
+    fn decoder(limits: DecoderLimits) -> Decoder {
+        Decoder {
+            limits,
+            ..Decoder::default()
+        }
+    }
+
+    /// `depth` `sum` packets nested in each other around a single literal
+    fn nested(depth: usize) -> BitStream {
+        let mut bits = BitStream::empty();
+        for _ in 0..depth {
+            // A `sum` packet counting one sub packet
+            bits.emit(0b0000001, 7);
+            bits.emit(1, 11);
+        }
+        bits.emit(0b000100, 6);
+        bits.emit(0b00111, 5);
+        bits
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let limits = DecoderLimits {
+            max_depth: Some(3),
+            ..DecoderLimits::unlimited()
+        };
+        assert!(decoder(limits).decode(nested(3)).is_ok());
+        let err = decoder(limits).decode(nested(4)).unwrap_err();
+        assert!(matches!(err, ParsingError::DepthLimitExceeded(3, 72)));
+        assert_eq!(Some(72), err.position());
+
+        // Fails instead of overflowing the stack
+        let err = decoder(DecoderLimits::untrusted())
+            .decode(nested(100_000))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ParsingError::DepthLimitExceeded(UNTRUSTED_MAX_DEPTH, _)
+        ));
+    }
+
+    #[test]
+    fn test_unlimited_depth() {
+        // Decoding without limits does not overflow the stack either
+        let depth = 50_000;
+        let packet = parse_bit_stream(nested(depth)).unwrap();
+        assert_eq!(depth, depth_stats(&packet).max_depth);
+        assert_eq!(Ok(7), try_apply_ops(&packet));
+
+        let hex = nested(depth).to_hex();
+        let packets: Vec<_> = PacketReader::hex(hex.as_bytes()).collect();
+        assert_eq!(1, packets.len());
+        assert!(packets[0].as_ref().unwrap() == &packet);
+
+        let partial = Decoder::new().decode_lenient(nested(depth));
+        assert!(partial.diagnostics.is_empty());
+        assert!(partial.packet.unwrap() == packet);
+    }
+
+    #[test]
+    fn test_max_packets() {
+        let bits = encode_packet(
+            &"(sum (lit 1) (lit 2) (lit 3))".parse().unwrap(),
+            LengthTyp::NumSubPackets,
+        )
+        .unwrap();
+        let limits = DecoderLimits {
+            max_packets: Some(4),
+            ..DecoderLimits::unlimited()
+        };
+        assert!(decoder(limits).decode(bits.clone()).is_ok());
+
+        let limits = DecoderLimits {
+            max_packets: Some(3),
+            ..DecoderLimits::unlimited()
+        };
+        assert!(matches!(
+            decoder(limits).decode(bits),
+            Err(ParsingError::PacketLimitExceeded(3, 40))
+        ));
+
+        // All top-level packets count against the limit
+        let limits = DecoderLimits {
+            max_packets: Some(2),
+            ..DecoderLimits::unlimited()
+        };
+        let mut bits = BitStream::empty();
+        for _ in 0..3 {
+            bits.emit(0b000100, 6);
+            bits.emit(0b00111, 5);
+        }
+        assert!(matches!(
+            decoder(limits).decode_all(bits),
+            Err(ParsingError::PacketLimitExceeded(2, 22))
+        ));
+    }
+
+    #[test]
+    fn test_max_bits() {
+        let limits = DecoderLimits {
+            max_bits: Some(21),
+            ..DecoderLimits::unlimited()
+        };
+        assert_eq!(
+            Packet::new_literal_packet(6, 2021),
+            decoder(limits)
+                .decode("D2FE28".try_into().unwrap())
+                .unwrap()
+        );
+
+        let limits = DecoderLimits {
+            max_bits: Some(20),
+            ..DecoderLimits::unlimited()
+        };
+        let err = decoder(limits)
+            .decode("D2FE28".try_into().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, ParsingError::InputTooLong(20)));
+        assert_eq!(Some(20), err.position());
+    }
+
+    #[test]
+    fn test_max_bits_streamed_literal() {
+        // A single literal far longer than the limit, of which only the start may be read
+        let mut bits = BitStream::empty();
+        bits.emit(0b110100, 6);
+        for _ in 0..1_000_000 {
+            bits.emit(0b11111, 5);
+        }
+        bits.emit(0b00000, 5);
+        let hex = bits.to_hex();
+        let mut reader = PacketReader::hex(hex.as_bytes()).with_decoder(decoder(DecoderLimits {
+            max_bits: Some(1000),
+            ..DecoderLimits::unlimited()
+        }));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ParsingError::InputTooLong(1000)))
+        ));
+
+        let err = decoder(DecoderLimits::untrusted())
+            .decode_as::<num_bigint::BigUint>(bits)
+            .unwrap_err();
+        assert!(matches!(err, ParsingError::InputTooLong(_)));
+    }
+}
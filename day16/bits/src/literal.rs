@@ -0,0 +1,165 @@
+//! The integer types literal values can be decoded into and encoded from
+
+use crate::BITS_LITERAL_VAL;
+use num_bigint::BigUint;
+
+/// An integer type holding the values of `literal` packets
+pub trait LiteralValue: Default {
+    /// Append a group of bits to the least significant end of this value.
+    /// Returns `None` if the result does not fit into this type
+    fn push_group(self, group: u8) -> Option<Self>;
+
+    /// Split this value into groups of bits, most significant group first.
+    /// Has no leading zero groups, but always at least one group
+    fn groups(&self) -> Vec<u8>;
+}
+
+/// Implements `LiteralValue` for a primitive unsigned integer type
+macro_rules! impl_literal_value {
+    ($($typ: ty),*) => {
+        $(
+            impl LiteralValue for $typ {
+                fn push_group(self, group: u8) -> Option<Self> {
+                    if self.leading_zeros() < BITS_LITERAL_VAL as u32 {
+                        return None;
+                    }
+                    Some((self << BITS_LITERAL_VAL) | <$typ>::from(group))
+                }
+
+                fn groups(&self) -> Vec<u8> {
+                    let bits_needed = (<$typ>::BITS - self.leading_zeros()) as usize;
+                    let num_groups = bits_needed.div_ceil(BITS_LITERAL_VAL).max(1);
+                    let mask = (1 << BITS_LITERAL_VAL) - 1;
+                    (0..num_groups)
+                        .rev()
+                        .map(|group| ((self >> (group * BITS_LITERAL_VAL)) & mask) as u8)
+                        .collect()
+                }
+            }
+        )*
+    };
+}
+
+impl_literal_value!(u64, u128);
+
+impl LiteralValue for BigUint {
+    fn push_group(self, group: u8) -> Option<Self> {
+        Some((self << BITS_LITERAL_VAL) | BigUint::from(group))
+    }
+
+    fn groups(&self) -> Vec<u8> {
+        self.to_radix_be(1 << BITS_LITERAL_VAL)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        apply_ops_big, encode_hex, encode_packet, encoded_len, version_sum, BitStream, Decoder,
+        LengthTyp, Packet, ParsingError,
+    };
+
+    /// A literal packet of version `1` made of `groups` groups of all one bits
+    fn long_literal(groups: usize) -> BitStream {
+        let mut bits = BitStream::empty();
+        bits.emit(0b001_100, 6);
+        for group in (0..groups).rev() {
+            bits.emit((group != 0).into(), 1);
+            bits.emit(0b1111, BITS_LITERAL_VAL);
+        }
+        bits
+    }
+
+    #[test]
+    fn test_literal_widths() {
+        let decoder = Decoder::new();
+        assert_eq!(
+            Packet::new_literal_packet(1, u64::MAX),
+            decoder.decode_as::<u64>(long_literal(16)).unwrap()
+        );
+        assert!(matches!(
+            decoder.decode_as::<u64>(long_literal(17)),
+            Err(ParsingError::Overflow)
+        ));
+        assert_eq!(
+            Packet::new_literal_packet(1, (1u128 << 68) - 1),
+            decoder.decode_as::<u128>(long_literal(17)).unwrap()
+        );
+        assert!(matches!(
+            decoder.decode(long_literal(33)),
+            Err(ParsingError::Overflow)
+        ));
+
+        let big = decoder.decode_as::<BigUint>(long_literal(40)).unwrap();
+        let expected: BigUint = (BigUint::from(1u8) << 160) - 1u8;
+        assert_eq!(Packet::new_literal_packet(1, expected.clone()), big);
+        assert_eq!(Ok(expected), apply_ops_big(&big));
+        assert_eq!(1, version_sum(&big));
+    }
+
+    #[test]
+    fn test_literal_round_trip() {
+        assert_eq!(vec![0], 0u64.groups());
+        assert_eq!(vec![7, 14, 5], 2021u128.groups());
+        assert_eq!(vec![0xf; 32], u128::MAX.groups());
+        assert_eq!(vec![0], BigUint::default().groups());
+        assert_eq!(vec![7, 14, 5], BigUint::from(2021u16).groups());
+
+        let bits = long_literal(40);
+        let big = Decoder::new().decode_as::<BigUint>(bits.clone()).unwrap();
+        assert_eq!(
+            "(lit@1 1461501637330902918203684832716283019655932542975)",
+            big.to_string()
+        );
+        let encoded = encode_packet(&big, LengthTyp::NumSubPackets).unwrap();
+        assert_eq!(bits.to_hex(), encoded.to_hex());
+        assert_eq!(
+            bits.backing.len(),
+            encoded_len(&big, LengthTyp::NumSubPackets)
+        );
+
+        let small = Decoder::new().decode_as::<u64>(long_literal(3)).unwrap();
+        assert_eq!("(lit@1 4095)", small.to_string());
+        assert_eq!(
+            long_literal(3).to_hex(),
+            encode_hex(&small, LengthTyp::LengthInBits).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_big_literal_evaluation() {
+        let mut bits = BitStream::empty();
+        // A `sum` packet counting two sub packets
+        bits.emit(0b0000001, 7);
+        bits.emit(2, 11);
+        bits.backing.extend_from_bitslice(&long_literal(40).backing);
+        bits.backing.extend_from_bitslice(&long_literal(1).backing);
+        let packet = Decoder::new().decode_as::<BigUint>(bits).unwrap();
+        assert_eq!(
+            Ok((BigUint::from(1u8) << 160) + 14u8),
+            apply_ops_big(&packet)
+        );
+    }
+
+    #[test]
+    fn test_max_literal_groups() {
+        let mut decoder = Decoder::new();
+        decoder.max_literal_groups = Some(3);
+        assert_eq!(
+            Packet::new_literal_packet(6, 2021),
+            decoder.decode("D2FE28".try_into().unwrap()).unwrap()
+        );
+
+        decoder.max_literal_groups = Some(2);
+        let err = decoder.decode("D2FE28".try_into().unwrap()).unwrap_err();
+        assert!(matches!(err, ParsingError::LiteralTooLong(2, 0)));
+        assert_eq!(Some(0), err.position());
+
+        // The limit applies before the literal is read completely
+        let err = decoder
+            .decode_as::<BigUint>(long_literal(10_000))
+            .unwrap_err();
+        assert!(matches!(err, ParsingError::LiteralTooLong(2, 0)));
+    }
+}
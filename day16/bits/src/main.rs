@@ -0,0 +1,113 @@
+//! A command line tool for `BITS` transmissions.
+//!
+//! Every subcommand reads it's input from the file given as argument,
+//! or from stdin if there is none or it is `-`:
+//!
+//! - `decode`: print the packet tree of a hex transmission
+//! - `eval`: print the value of a hex transmission
+//! - `versions`: print the sum of all versions in a hex transmission
+//! - `encode`: print the hex transmission of a packet tree in the expression syntax
+//! - `inspect`: print every field decoded from a hex transmission
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+
+use bits::{
+    encode_hex, try_apply_ops, version_sum, BitStream, Decoder, DecoderLimits, LengthTyp, Packet,
+};
+use std::io::Read;
+use std::process::exit;
+
+/// The usage message printed on invalid arguments
+const USAGE: &str = "usage: bits <decode|eval|versions|encode|inspect> [FILE]";
+
+/// The subcommands understood
+const COMMANDS: [&str; 5] = ["decode", "eval", "versions", "encode", "inspect"];
+
+/// Print `msg` to stderr and exit with a failure code
+fn fail(msg: String) -> ! {
+    eprintln!("{}", msg);
+    exit(1)
+}
+
+/// Read the whole input from the file at `path`, or from stdin if `path` is `None` or `-`
+fn read_input(path: Option<&str>) -> String {
+    let mut ret = String::new();
+    match path {
+        None | Some("-") => std::io::stdin()
+            .read_to_string(&mut ret)
+            .unwrap_or_else(|e| fail(format!("can not read stdin: {}", e))),
+        Some(path) => std::fs::File::open(path)
+            .and_then(|mut fd| fd.read_to_string(&mut ret))
+            .unwrap_or_else(|e| fail(format!("can not read {}: {}", path, e))),
+    };
+    ret
+}
+
+/// A decoder safe to use on transmissions from any source
+fn decoder() -> Decoder {
+    Decoder {
+        limits: DecoderLimits::untrusted(),
+        ..Decoder::default()
+    }
+}
+
+/// Parse the hex transmission `input`, which may be split across lines
+fn parse_bits(input: &str) -> BitStream {
+    let hex: String = input.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    BitStream::try_from(hex.as_str())
+        .unwrap_or_else(|e| fail(format!("invalid transmission: {:?}", e)))
+}
+
+/// Decode the hex transmission `input` into a packet tree
+fn decode(input: &str) -> Packet {
+    let bits = parse_bits(input);
+    decoder().decode(bits.clone()).unwrap_or_else(|e| {
+        let msg = match e.position() {
+            Some(pos) => format!("{:?}\n{}", e, bits.hex_dump_pointer(pos)),
+            None => format!("{:?}", e),
+        };
+        fail(format!("can not decode transmission: {}", msg))
+    })
+}
+
+/// Print every field decoded from the hex transmission `input`
+fn inspect(input: &str) {
+    let inspection = decoder().inspect(parse_bits(input));
+    println!("{}", inspection);
+    if inspection.result.is_err() {
+        exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, path) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [command] => (command, None),
+        [command, path] => (command, Some(path)),
+        _ => fail(USAGE.to_string()),
+    };
+    if !COMMANDS.contains(&command) {
+        fail(USAGE.to_string());
+    }
+    let input = read_input(path);
+    match command {
+        "decode" => println!("{:#}", decode(&input)),
+        "eval" => {
+            let res = try_apply_ops(&decode(&input))
+                .unwrap_or_else(|e| fail(format!("can not evaluate: {:?}", e)));
+            println!("{}", res);
+        }
+        "versions" => println!("{}", version_sum(&decode(&input))),
+        "encode" => {
+            let packet: Packet = input
+                .trim()
+                .parse()
+                .unwrap_or_else(|e| fail(format!("invalid expression: {:?}", e)));
+            let hex = encode_hex(&packet, LengthTyp::NumSubPackets)
+                .unwrap_or_else(|e| fail(format!("can not encode: {:?}", e)));
+            println!("{}", hex);
+        }
+        "inspect" => inspect(&input),
+        _ => unreachable!("checked against COMMANDS"),
+    }
+}
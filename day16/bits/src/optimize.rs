@@ -0,0 +1,215 @@
+//! Simplifying `BITS` packet trees without changing their value
+//!
+//! The optimizer folds operations on literals into a single literal,
+//! flattens nested `sum` and `product` packets, drops identity operands
+//! and replaces operations on a single operand by that operand.
+//! The versions of removed packets are lost.
+
+use crate::{
+    encoded_len, fold, node_count, try_apply_ops, LengthTyp, Operand, Packet, PacketFold, Payload,
+};
+
+/// How much an optimization shrunk a packet tree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizeStats {
+    /// The number of packets before optimizing
+    pub packets_before: usize,
+
+    /// The number of packets after optimizing
+    pub packets_after: usize,
+
+    /// The encoded length in bits before optimizing
+    pub bits_before: usize,
+
+    /// The encoded length in bits after optimizing
+    pub bits_after: usize,
+}
+
+impl OptimizeStats {
+    /// The number of packets removed
+    pub fn packets_removed(&self) -> usize {
+        self.packets_before.saturating_sub(self.packets_after)
+    }
+
+    /// The number of bits saved when encoding the optimized tree
+    pub fn bits_saved(&self) -> usize {
+        self.bits_before.saturating_sub(self.bits_after)
+    }
+}
+
+/// Rebuilds a packet tree bottom-up, simplifying each operation
+struct Simplifier;
+
+impl Simplifier {
+    /// Replace sub packets with the same associative operation `op` by their sub packets
+    fn flatten(op: Operand, childs: Vec<Packet>) -> Vec<Packet> {
+        let mut ret = Vec::with_capacity(childs.len());
+        for mut child in childs {
+            match &mut child.payload {
+                Payload::Op(child_op, grand_childs) if *child_op == op => {
+                    ret.append(grand_childs);
+                }
+                _ => ret.push(child),
+            }
+        }
+        ret
+    }
+
+    /// Returns whether `packet` is a literal that does not change the result of `op`
+    fn is_identity(op: Operand, packet: &Packet) -> bool {
+        matches!(
+            (op, &packet.payload),
+            (Operand::Sum, Payload::Literal(0)) | (Operand::Product, Payload::Literal(1))
+        )
+    }
+}
+
+impl PacketFold for Simplifier {
+    type Output = Packet;
+
+    fn literal(&mut self, packet: &Packet, val: u128) -> Packet {
+        Packet::new_literal_packet(packet.version, val)
+    }
+
+    fn op(&mut self, packet: &Packet, op: Operand, childs: Vec<Packet>) -> Packet {
+        let mut childs = match op {
+            Operand::Sum | Operand::Product => Self::flatten(op, childs),
+            _ => childs,
+        };
+        childs.retain(|child| !Self::is_identity(op, child));
+
+        let is_constant = childs
+            .iter()
+            .all(|child| matches!(child.payload, Payload::Literal(_)));
+        let mut ret = Packet::new_op_packet(packet.version, op, childs);
+        // Operations that overflow or can not be evaluated are kept as they are
+        if is_constant {
+            if let Ok(val) = try_apply_ops(&ret) {
+                return Packet::new_literal_packet(packet.version, val);
+            }
+        }
+
+        let is_unary = matches!(
+            op,
+            Operand::Sum | Operand::Product | Operand::Minimum | Operand::Maximum
+        );
+        match &mut ret.payload {
+            Payload::Op(_, childs) if is_unary && childs.len() == 1 => {
+                childs.pop().expect("checked there is one sub packet")
+            }
+            _ => ret,
+        }
+    }
+}
+
+/// Simplify the packet tree.
+/// The result evaluates to the same value with `apply_ops`.
+/// If `try_apply_ops` succeeds on the tree, it succeeds with the same value on the result
+pub fn optimize(packet: &Packet) -> Packet {
+    fold(packet, &mut Simplifier)
+}
+
+/// Simplify the packet tree like `optimize`, reporting how much it shrunk.
+/// The encoded lengths assume every operator packet counts it's sub packets
+pub fn optimize_with_stats(packet: &Packet) -> (Packet, OptimizeStats) {
+    let ret = optimize(packet);
+    let stats = OptimizeStats {
+        packets_before: node_count(packet).total(),
+        packets_after: node_count(&ret).total(),
+        bits_before: encoded_len(packet, LengthTyp::NumSubPackets),
+        bits_after: encoded_len(&ret, LengthTyp::NumSubPackets),
+    };
+    (ret, stats)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::random_packets;
+    use crate::{apply_ops, parse_bit_stream, parse_expr, Arity, CustomOperator, OperatorRegistry};
+
+    /// Parse the expression `s`, with `custom5` and `custom6` as operations that can not be folded
+    fn parse(s: &str) -> Packet {
+        let mut registry = OperatorRegistry::new();
+        for typ in [5, 6] {
+            let op = CustomOperator::new("opaque", Arity::Any, |_| None);
+            registry.register(typ, op).unwrap();
+        }
+        parse_expr(s, &registry).unwrap()
+    }
+
+    /// Optimize the expression `s`
+    fn simplify(s: &str) -> Packet {
+        optimize(&parse(s))
+    }
+
+    #[test]
+    fn test_simplify() {
+        for (s, expected) in [
+            ("(sum (lit 1) (lit 2))", "(lit 3)"),
+            ("(eq@2 (lit 1) (sum (lit 1) (lit 0)))", "(lit@2 1)"),
+            (
+                "(sum (lit@1 1) (sum (lit@2 2) (sum (lit@3 3) (custom5))))",
+                "(sum (lit@1 1) (lit@2 2) (lit@3 3) (custom5))",
+            ),
+            (
+                "(product (lit 1) (custom5) (sum (lit 0)))",
+                "(product (custom5) (lit 0))",
+            ),
+            (
+                "(sum (product (custom5) (lit 2)) (lit 0))",
+                "(product (custom5) (lit 2))",
+            ),
+            ("(max (min (custom5)))", "(custom5)"),
+            ("(lt (custom5) (custom6))", "(lt (custom5) (custom6))"),
+            (
+                "(gt (min (lit 2)) (max (lit 1) (custom5)))",
+                "(gt (lit 2) (max (lit 1) (custom5)))",
+            ),
+            (
+                "(product (lit 340282366920938463463374607431768211455) (lit 2))",
+                "(product (lit 340282366920938463463374607431768211455) (lit 2))",
+            ),
+        ] {
+            assert_eq!(parse(expected), simplify(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let packet = "(sum (lit 1) (sum (lit 2) (lit 3)))".parse().unwrap();
+        let (optimized, stats) = optimize_with_stats(&packet);
+        assert_eq!(Packet::new_literal_packet(0, 6), optimized);
+        assert_eq!(
+            OptimizeStats {
+                packets_before: 5,
+                packets_after: 1,
+                bits_before: 18 + 11 + 18 + 11 + 11,
+                bits_after: 11,
+            },
+            stats
+        );
+        assert_eq!(4, stats.packets_removed());
+        assert_eq!(58, stats.bits_saved());
+    }
+
+    #[test]
+    fn test_values_unchanged() {
+        let fd = std::fs::File::open("input").unwrap();
+        let input = std::io::read_to_string(fd).unwrap();
+        let packet = parse_bit_stream(input.trim().try_into().unwrap()).unwrap();
+        let (optimized, stats) = optimize_with_stats(&packet);
+        assert_eq!(apply_ops(&packet), apply_ops(&optimized));
+        assert_eq!(Payload::Literal(831996589851), optimized.payload);
+        assert!(stats.bits_saved() > 0);
+
+        for (seed, packet) in random_packets() {
+            let optimized = optimize(&packet);
+            assert_eq!(apply_ops(&packet), apply_ops(&optimized), "seed {}", seed);
+            if let Ok(val) = try_apply_ops(&packet) {
+                assert_eq!(Ok(val), try_apply_ops(&optimized), "seed {}", seed);
+            }
+            assert!(node_count(&optimized).total() <= node_count(&packet).total());
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Decoding `BITS` packets leniently, collecting errors instead of failing on the first one
+//!
+//! Recoverable errors are reported as diagnostics and replaced by placeholders:
+//! an unknown operator type id is kept as `Operand::Custom`,
+//! a literal that does not fit into a `u128` becomes `0`,
+//! and an operation with the wrong number of sub packets is kept as it is.
+//! A stream ending early keeps all packets decoded up to that point.
+
+use crate::{parse_bits_intern, BitStream, Decoder, Packet, ParsingError};
+
+/// An error found while decoding leniently
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// The bit position of the packet the error was found in
+    pub offset: usize,
+
+    /// The error found
+    pub error: ParsingError,
+}
+
+/// The result of decoding leniently
+#[derive(Debug)]
+pub struct PartialDecode {
+    /// The decoded packet tree, `None` if not even the root packet could be decoded
+    pub packet: Option<Packet>,
+
+    /// The errors found, in the order they were found
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The state of decoding a single packet tree: whether errors abort decoding,
+/// the errors collected if not, and how many packets were decoded
+#[derive(Debug, Default)]
+pub(crate) struct DecodeState {
+    /// Whether errors are collected instead of aborting decoding
+    lenient: bool,
+
+    /// The errors collected so far
+    diagnostics: Vec<Diagnostic>,
+
+    /// Whether the stream ended early, so no further packets can be decoded
+    truncated: bool,
+
+    /// The number of packets decoded so far, counted against the decoder limits
+    pub(crate) packets: usize,
+}
+
+impl DecodeState {
+    /// Abort decoding on the first error
+    pub(crate) fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Collect errors and keep decoding
+    fn lenient() -> Self {
+        Self {
+            lenient: true,
+            ..Self::default()
+        }
+    }
+
+    /// Report an error found in the packet at `offset` that decoding can recover from.
+    /// Fails with `error` in strict mode
+    pub(crate) fn report(
+        &mut self,
+        offset: usize,
+        error: ParsingError,
+    ) -> Result<(), ParsingError> {
+        if !self.lenient {
+            return Err(error);
+        }
+        self.diagnostics.push(Diagnostic { offset, error });
+        Ok(())
+    }
+
+    /// Report that decoding the packet at `offset` failed with `error`,
+    /// so no further packets can be decoded. Fails with `error` in strict mode
+    pub(crate) fn truncate(
+        &mut self,
+        offset: usize,
+        error: ParsingError,
+    ) -> Result<(), ParsingError> {
+        self.report(offset, error)?;
+        self.truncated = true;
+        Ok(())
+    }
+
+    /// Returns whether no further packets can be decoded
+    pub(crate) fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl Decoder {
+    /// Parse the first packet of a stream of bits into a `BITS` packet tree,
+    /// recovering from as many errors as possible.
+    /// Returns the partial packet tree together with all errors found
+    pub fn decode_lenient(&self, mut stream: BitStream) -> PartialDecode {
+        let mut state = DecodeState::lenient();
+        let mut ret = Vec::new();
+        if let Err(e) = parse_bits_intern(&mut stream, &mut ret, self, &mut state) {
+            state.truncate(0, e).expect("lenient recovery never fails");
+        }
+        if !state.is_truncated() {
+            if let Err(e) = self.check_padding(&stream) {
+                state
+                    .report(stream.idx, e)
+                    .expect("lenient recovery never fails");
+            }
+        }
+        PartialDecode {
+            packet: ret.pop(),
+            diagnostics: state.diagnostics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{encode_packet, try_apply_ops, EvalError, LengthTyp, Operand, OperatorRegistry};
+
+    /// Encode the expression `s`, counting sub packets
+    fn encode(s: &str) -> BitStream {
+        encode_packet(&s.parse().unwrap(), LengthTyp::NumSubPackets).unwrap()
+    }
+
+    #[test]
+    fn test_wrong_operand_count() {
+        let lit = |val: u128| Packet::new_literal_packet(0, val);
+        let eq = Packet::new_op_packet(0, Operand::Equals, vec![lit(1), lit(1), lit(1)]);
+        let bits = encode_packet(&eq, LengthTyp::NumSubPackets).unwrap();
+        assert!(matches!(
+            Decoder::new().decode(bits),
+            Err(ParsingError::InvalidOperandCount(Operand::Equals, 3))
+        ));
+
+        let lt = Packet::new_op_packet(0, Operand::LessThan, vec![lit(2)]);
+        let sum = Packet::new_op_packet(0, Operand::Sum, vec![lit(1), eq, lt]);
+        let bits = encode_packet(&sum, LengthTyp::LengthInBits).unwrap();
+        let decoded = Decoder::new().decode_lenient(bits);
+        let packet = decoded.packet.unwrap();
+        assert_eq!(
+            "(sum@0 (lit@0 1) (eq@0 (lit@0 1) (lit@0 1) (lit@0 1)) (lt@0 (lit@0 2)))",
+            packet.to_string()
+        );
+        assert_eq!(2, decoded.diagnostics.len());
+        assert_eq!(33, decoded.diagnostics[0].offset);
+        assert!(matches!(
+            decoded.diagnostics[0].error,
+            ParsingError::InvalidOperandCount(Operand::Equals, 3)
+        ));
+        assert!(matches!(
+            decoded.diagnostics[1].error,
+            ParsingError::InvalidOperandCount(Operand::LessThan, 1)
+        ));
+        assert_eq!(
+            Err(EvalError::InvalidOperandCount(Operand::Equals, 3)),
+            try_apply_ops(&packet)
+        );
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let mut registry = OperatorRegistry::new();
+        registry.unregister(crate::MINIMUM_PACKET_TYPE).unwrap();
+        let decoder = Decoder {
+            registry,
+            ..Decoder::default()
+        };
+        let decoded = decoder.decode_lenient(encode("(sum (min (lit 3) (lit 4)) (lit 5))"));
+        assert_eq!(
+            "(sum@0 (custom2@0 (lit@0 3) (lit@0 4)) (lit@0 5))",
+            decoded.packet.unwrap().to_string()
+        );
+        assert!(matches!(
+            decoded.diagnostics[..],
+            [Diagnostic {
+                offset: 18,
+                error: ParsingError::InvalidOperand(2, 21)
+            }]
+        ));
+
+        let mut bits = BitStream::empty();
+        // A literal of 33 groups does not fit into a `u128`
+        bits.emit(0b100, 6);
+        for _ in 0..32 {
+            bits.emit(0b11111, 5);
+        }
+        bits.emit(0b01111, 5);
+        let decoded = Decoder::new().decode_lenient(bits);
+        assert_eq!(Some(Packet::new_literal_packet(0, 0)), decoded.packet);
+        assert!(matches!(
+            decoded.diagnostics[..],
+            [Diagnostic {
+                offset: 0,
+                error: ParsingError::Overflow
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bits = encode("(sum (lit 1) (product (lit 2) (lit 3)) (lit 4))");
+        let hex = bits.to_hex();
+        let truncated: BitStream = hex[..hex.len() - 4].try_into().unwrap();
+
+        assert!(matches!(
+            Decoder::new().decode(truncated.clone()),
+            Err(ParsingError::OOB(_))
+        ));
+        let decoded = Decoder::new().decode_lenient(truncated);
+        assert_eq!(
+            "(sum@0 (lit@0 1) (product@0 (lit@0 2)))",
+            decoded.packet.unwrap().to_string()
+        );
+        assert_eq!(1, decoded.diagnostics.len());
+        assert!(matches!(decoded.diagnostics[0].error, ParsingError::OOB(_)));
+
+        let decoded = Decoder::new().decode_lenient("D2".try_into().unwrap());
+        assert!(decoded.packet.is_none());
+        assert_eq!(1, decoded.diagnostics.len());
+
+        let decoded = Decoder::strict().decode_lenient("D2FE28FF".try_into().unwrap());
+        assert_eq!(Some(Packet::new_literal_packet(6, 2021)), decoded.packet);
+        assert!(matches!(
+            decoded.diagnostics[..],
+            [Diagnostic {
+                offset: 21,
+                error: ParsingError::NonZeroPadding
+            }]
+        ));
+    }
+}
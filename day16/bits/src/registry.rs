@@ -0,0 +1,301 @@
+//! A registry mapping the `type` field of operator packets to operations
+//!
+//! Besides the built-in operations, custom operations with their own arity rules
+//! and evaluation functions can be registered for any operator type id,
+//! to decode and evaluate extended `BITS` dialects.
+
+use crate::{Operand, BITS_TYP, LITERAL_PACKET_TYP};
+use std::fmt;
+use std::sync::Arc;
+
+/// The number of distinct values of the `type` field
+const NUM_TYPS: usize = 1 << BITS_TYP;
+
+/// The number of sub packets an operation accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// Any number of sub packets, including none
+    Any,
+
+    /// Exactly the given number of sub packets
+    Exactly(usize),
+
+    /// At least the given number of sub packets
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Returns whether an operation with this arity accepts `count` sub packets
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Any => true,
+            Arity::Exactly(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+        }
+    }
+}
+
+/// The function evaluating a custom operation from the values of it's sub packets.
+/// Returns `None` if the operation has no result for these values
+pub type EvalFn = dyn Fn(&[u128]) -> Option<u128> + Send + Sync;
+
+/// A custom operation that can be registered for an operator type id
+#[derive(Clone)]
+pub struct CustomOperator {
+    /// The name of the operation, used for it in the expression syntax
+    pub name: String,
+
+    /// The number of sub packets the operation accepts
+    pub arity: Arity,
+
+    /// The function evaluating the operation
+    pub eval: Arc<EvalFn>,
+}
+
+impl CustomOperator {
+    /// Construct a new custom operation
+    pub fn new<F>(name: &str, arity: Arity, eval: F) -> Self
+    where
+        F: Fn(&[u128]) -> Option<u128> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            arity,
+            eval: Arc::new(eval),
+        }
+    }
+}
+
+impl fmt::Debug for CustomOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomOperator")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+/// Errors that can occur while registering operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The type id is reserved for `literal` packets
+    ReservedTyp(u8),
+
+    /// The type id does not fit into the `type` field
+    TypOutOfRange(u8),
+}
+
+/// What an operator type id is decoded as
+#[derive(Debug, Clone)]
+enum Entry {
+    /// The type id is not a valid operation
+    Invalid,
+
+    /// One of the built-in operations
+    Builtin(Operand),
+
+    /// A registered custom operation
+    Custom(CustomOperator),
+}
+
+/// Maps the `type` field of operator packets to operations
+#[derive(Debug, Clone)]
+pub struct OperatorRegistry {
+    /// The operation of each type id
+    entries: [Entry; NUM_TYPS],
+}
+
+impl Default for OperatorRegistry {
+    /// A registry holding only the built-in operations
+    fn default() -> Self {
+        let entries = std::array::from_fn(|typ| match Operand::try_from(typ as u8) {
+            Ok(op) => Entry::Builtin(op),
+            Err(_) => Entry::Invalid,
+        });
+        Self { entries }
+    }
+}
+
+impl OperatorRegistry {
+    /// Construct a registry holding only the built-in operations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check that `typ` can hold an operation
+    fn check_typ(typ: u8) -> Result<usize, RegistryError> {
+        if typ as usize >= NUM_TYPS {
+            return Err(RegistryError::TypOutOfRange(typ));
+        }
+        if typ == LITERAL_PACKET_TYP {
+            return Err(RegistryError::ReservedTyp(typ));
+        }
+        Ok(typ as usize)
+    }
+
+    /// Register a custom operation for the type id `typ`,
+    /// replacing any operation registered for it before
+    pub fn register(&mut self, typ: u8, op: CustomOperator) -> Result<&mut Self, RegistryError> {
+        let idx = Self::check_typ(typ)?;
+        self.entries[idx] = Entry::Custom(op);
+        Ok(self)
+    }
+
+    /// Remove the operation of the type id `typ`, so decoding it fails
+    pub fn unregister(&mut self, typ: u8) -> Result<&mut Self, RegistryError> {
+        let idx = Self::check_typ(typ)?;
+        self.entries[idx] = Entry::Invalid;
+        Ok(self)
+    }
+
+    /// The operation decoded from the type id `typ`, if any
+    pub fn operand(&self, typ: u8) -> Option<Operand> {
+        match self.entries.get(typ as usize)? {
+            Entry::Invalid => None,
+            Entry::Builtin(op) => Some(*op),
+            Entry::Custom(_) => Some(Operand::Custom(typ)),
+        }
+    }
+
+    /// The type id of the first custom operation registered with the name `name`, if any
+    pub fn find_custom(&self, name: &str) -> Option<u8> {
+        (0..NUM_TYPS as u8).find(|&typ| matches!(self.custom(typ), Some(op) if op.name == name))
+    }
+
+    /// The custom operation registered for the type id `typ`, if any
+    pub fn custom(&self, typ: u8) -> Option<&CustomOperator> {
+        match self.entries.get(typ as usize)? {
+            Entry::Custom(op) => Some(op),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        encode_packet, parse_expr, Decoder, EvalError, LengthTyp, Packet, ParsingError,
+        EQ_PACKET_TYPE, GREATER_THAN_PACKET_TYPE, LESS_THAN_PACKET_TYPE, MAXIMUM_PACKET_TYPE,
+        MINIMUM_PACKET_TYPE, PRODUCT_PACKET_TYPE, SUM_PACKET_TYP,
+    };
+
+    /// A registry with `xor`, `modulo` and `conditional` replacing `min`, `max` and `gt`
+    fn dialect() -> OperatorRegistry {
+        let mut registry = OperatorRegistry::new();
+        registry
+            .register(
+                MINIMUM_PACKET_TYPE,
+                CustomOperator::new("xor", Arity::Any, |vals| {
+                    Some(vals.iter().fold(0, |acc, x| acc ^ x))
+                }),
+            )
+            .unwrap()
+            .register(
+                MAXIMUM_PACKET_TYPE,
+                CustomOperator::new("modulo", Arity::Exactly(2), |vals| {
+                    vals[0].checked_rem(vals[1])
+                }),
+            )
+            .unwrap()
+            .register(
+                GREATER_THAN_PACKET_TYPE,
+                CustomOperator::new("conditional", Arity::Exactly(3), |vals| {
+                    Some(if vals[0] != 0 { vals[1] } else { vals[2] })
+                }),
+            )
+            .unwrap();
+        registry
+    }
+
+    /// Encode and decode the expression `s` with the registry `registry`
+    fn round_trip(s: &str, registry: &OperatorRegistry) -> Result<Packet, ParsingError> {
+        let packet = parse_expr(s, registry).unwrap();
+        let bits = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+        let decoder = Decoder {
+            registry: registry.clone(),
+            ..Decoder::default()
+        };
+        decoder.decode(bits)
+    }
+
+    #[test]
+    fn test_custom_operators() {
+        let registry = dialect();
+        let packet = round_trip(
+            "(sum (custom2 (lit 5) (lit 3) (lit 1)) (custom3 (lit 17) (lit 5)))",
+            &registry,
+        )
+        .unwrap();
+        assert_eq!(Ok(7 + 2), registry.evaluate(&packet));
+
+        let packet = round_trip(
+            "(custom5 (eq (lit 1) (lit 1)) (lit 10) (lit 20))",
+            &registry,
+        )
+        .unwrap();
+        assert_eq!(Ok(10), registry.evaluate(&packet));
+
+        let packet = round_trip("(custom3 (lit 17) (lit 0))", &registry).unwrap();
+        assert_eq!(
+            Err(EvalError::Undefined(Operand::Custom(MAXIMUM_PACKET_TYPE))),
+            registry.evaluate(&packet)
+        );
+        assert_eq!(
+            Err(EvalError::UnknownOperator(MAXIMUM_PACKET_TYPE)),
+            OperatorRegistry::new().evaluate(&packet)
+        );
+    }
+
+    #[test]
+    fn test_builtins_unchanged() {
+        let packet = round_trip("(min (lit 5) (lit 3))", &OperatorRegistry::new()).unwrap();
+        assert_eq!(
+            Packet::new_op_packet(
+                0,
+                Operand::Minimum,
+                vec![
+                    Packet::new_literal_packet(0, 5),
+                    Packet::new_literal_packet(0, 3)
+                ]
+            ),
+            packet
+        );
+
+        let packet = round_trip("(min (lit 5) (lit 3))", &dialect()).unwrap();
+        assert_eq!(Ok(6), dialect().evaluate(&packet));
+    }
+
+    #[test]
+    fn test_registry_errors() {
+        assert!(matches!(
+            round_trip("(custom3 (lit 1))", &dialect()),
+            Err(ParsingError::InvalidOperandCount(Operand::Custom(3), 1))
+        ));
+
+        let mut registry = OperatorRegistry::new();
+        registry.unregister(SUM_PACKET_TYP).unwrap();
+        assert!(matches!(
+            round_trip("(sum (lit 1))", &registry),
+            Err(ParsingError::InvalidOperand(SUM_PACKET_TYP, 3))
+        ));
+
+        let op = CustomOperator::new("noop", Arity::Any, |_| Some(0));
+        assert_eq!(
+            Err(RegistryError::ReservedTyp(LITERAL_PACKET_TYP)),
+            registry
+                .register(LITERAL_PACKET_TYP, op.clone())
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(RegistryError::TypOutOfRange(8)),
+            registry.register(8, op).map(|_| ())
+        );
+        assert_eq!(None, registry.operand(PRODUCT_PACKET_TYPE + 100));
+        assert_eq!(Some(Operand::Equals), registry.operand(EQ_PACKET_TYPE));
+        assert_eq!(
+            Some(Operand::LessThan),
+            registry.operand(LESS_THAN_PACKET_TYPE)
+        );
+    }
+}
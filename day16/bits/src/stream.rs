@@ -0,0 +1,325 @@
+//! Decoding `BITS` packets incrementally from any reader
+
+use crate::recover::DecodeState;
+use crate::{
+    convert, parse_bits_intern, BitSource, Decoder, Packet, ParsingError, MIN_PACKET_BITS,
+};
+use bitvec::prelude::*;
+use std::io::Read;
+use std::ops::{BitXor, Shl};
+
+/// The number of bytes requested from the reader at once
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// The number of consumed bits after which the buffer gets compacted
+const COMPACT_THRESHOLD: usize = 8 * READ_CHUNK_SIZE;
+
+/// The encoding of the transmission read by a `PacketReader`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Hex digits, any whitespace in between is ignored
+    Hex,
+
+    /// Raw bytes
+    Binary,
+}
+
+/// The bits of a transmission read incrementally from a reader
+struct ReadBits<R> {
+    /// The reader the transmission is read from
+    reader: R,
+
+    /// The encoding of the transmission
+    format: InputFormat,
+
+    /// The bits read but not yet compacted away
+    buffer: BitVec<Msb0, u8>,
+
+    /// The current index into `buffer`
+    idx: usize,
+
+    /// The number of bits compacted away from the front of `buffer`
+    dropped: usize,
+
+    /// Whether `reader` is exhausted
+    eof: bool,
+}
+
+impl<R: Read> ReadBits<R> {
+    /// Read the next chunk from the reader into the buffer.
+    /// Returns `false` if the reader is exhausted
+    fn fill(&mut self) -> Result<bool, ParsingError> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let read = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        if read == 0 {
+            self.eof = true;
+            return Ok(false);
+        }
+
+        for byte in &chunk[..read] {
+            match self.format {
+                InputFormat::Binary => self.buffer.extend_from_bitslice(byte.view_bits::<Msb0>()),
+                InputFormat::Hex => {
+                    let c = *byte as char;
+                    if c.is_ascii_whitespace() {
+                        continue;
+                    }
+                    let nibble = c.to_digit(16).ok_or(ParsingError::InvalidHexDigit(c))?;
+                    for shift in (0..4).rev() {
+                        self.buffer.push((nibble >> shift) & 1 == 1);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Drop the already consumed bits from the buffer
+    fn compact(&mut self) {
+        if self.idx >= COMPACT_THRESHOLD {
+            self.buffer = self.buffer[self.idx..].to_bitvec();
+            self.dropped += self.idx;
+            self.idx = 0;
+        }
+    }
+
+    /// Returns whether only zero bits, or too few bits to hold a packet,
+    /// are left until the end of the transmission
+    fn at_padding(&mut self) -> Result<bool, ParsingError> {
+        while self.buffer.len() - self.idx < MIN_PACKET_BITS {
+            if !self.fill()? {
+                return Ok(true);
+            }
+        }
+        let mut checked = self.idx;
+        loop {
+            if self.buffer[checked..].any() {
+                return Ok(false);
+            }
+            checked = self.buffer.len();
+            if !self.fill()? {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: Read> BitSource for ReadBits<R> {
+    fn position(&self) -> usize {
+        self.dropped + self.idx
+    }
+
+    fn consume<T: From<bool> + BitXor<Output = T> + Shl<Output = T>>(
+        &mut self,
+        bits: usize,
+    ) -> Result<T, ParsingError> {
+        while self.buffer.len() - self.idx < bits {
+            if !self.fill()? {
+                return Err(ParsingError::OOB(self.position()));
+            }
+        }
+        let ret = convert(&self.buffer[self.idx..self.idx + bits]);
+        self.idx += bits;
+        ret
+    }
+}
+
+/// Decodes top-level packets one at a time from a reader.
+/// Only the bits of the packet currently being decoded are kept in memory.
+/// Like `Decoder::decode_all`, the reader stops once only zero bits,
+/// or too few bits to hold a packet, are left,
+/// so a packet made of only zero bits, an empty `sum` packet of version `0`
+/// giving the length of it's sub packets in bits, can not be told apart from padding
+/// and is never decoded
+pub struct PacketReader<R> {
+    /// The bits of the transmission
+    bits: ReadBits<R>,
+
+    /// The options used to decode each packet
+    decoder: Decoder,
+
+    /// Whether the iterator is exhausted, either by reaching the end or by an error
+    done: bool,
+}
+
+impl<R: Read> PacketReader<R> {
+    /// Construct a new reader decoding a transmission in `format`
+    pub fn new(reader: R, format: InputFormat) -> Self {
+        Self {
+            bits: ReadBits {
+                reader,
+                format,
+                buffer: BitVec::new(),
+                idx: 0,
+                dropped: 0,
+                eof: false,
+            },
+            decoder: Decoder::default(),
+            done: false,
+        }
+    }
+
+    /// Construct a new reader decoding a hex transmission
+    pub fn hex(reader: R) -> Self {
+        Self::new(reader, InputFormat::Hex)
+    }
+
+    /// Construct a new reader decoding a raw binary transmission
+    pub fn binary(reader: R) -> Self {
+        Self::new(reader, InputFormat::Binary)
+    }
+
+    /// Decode each packet using the options of `decoder`.
+    /// Padding checks do not apply, as decoding stops once only zero bits are left
+    pub fn with_decoder(self, decoder: Decoder) -> Self {
+        Self { decoder, ..self }
+    }
+
+    /// Decode the next top-level packet.
+    /// Returns `None` if only padding is left
+    fn next_packet(&mut self) -> Result<Option<Packet>, ParsingError> {
+        self.bits.compact();
+        if self.bits.at_padding()? {
+            return Ok(None);
+        }
+        let mut acc = Vec::new();
+        parse_bits_intern(
+            &mut self.bits,
+            &mut acc,
+            &self.decoder,
+            &mut DecodeState::strict(),
+        )?;
+        acc.pop().ok_or(ParsingError::EmptyPacketStream).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for PacketReader<R> {
+    type Item = Result<Packet, ParsingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let ret = self.next_packet().transpose();
+        if !matches!(ret, Some(Ok(_))) {
+            self.done = true;
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{apply_ops, encode_packet, parse_bit_stream, BitStream, LengthTyp, Span};
+
+    /// A reader handing out a single byte per call
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_single_packet() {
+        for s in ["D2FE28", "D2\nFE 28\n", "d2fe28"] {
+            let packets: Vec<_> = PacketReader::hex(s.as_bytes()).collect();
+            assert_eq!(1, packets.len());
+            assert_eq!(
+                &Packet::new_literal_packet(6, 2021),
+                packets[0].as_ref().unwrap()
+            );
+        }
+
+        let mut reader = PacketReader::binary(&[0xD2u8, 0xFE, 0x28][..]);
+        assert_eq!(
+            Packet::new_literal_packet(6, 2021),
+            reader.next().unwrap().unwrap()
+        );
+        assert!(reader.next().is_none());
+
+        let s = "9C0141080250320F1802104A08";
+        let mut reader = PacketReader::hex(ByteByByte(s.as_bytes()));
+        let packet = reader.next().unwrap().unwrap();
+        assert_eq!(parse_bit_stream(s.try_into().unwrap()).unwrap(), packet);
+        assert_eq!(
+            Some(Span {
+                offset: 0,
+                len: 102
+            }),
+            packet.span()
+        );
+        assert_eq!(1, apply_ops(&packet));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_read_multiple_packets() {
+        let exprs = [
+            "(sum@1 (lit@2 1) (lit@3 2))",
+            "(lit@6 2021)",
+            "(lt@1 (lit@6 10) (lit@2 20))",
+            "(sum (lit 0))",
+        ];
+        let mut bits = BitStream::empty();
+        for expr in exprs {
+            let packet = expr.parse().unwrap();
+            let encoded = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+            bits.backing.extend_from_bitslice(&encoded.backing);
+        }
+        let hex = bits.to_hex();
+
+        let packets = PacketReader::hex(ByteByByte(hex.as_bytes()))
+            .collect::<Result<Vec<Packet>, _>>()
+            .unwrap();
+        let expected = exprs
+            .iter()
+            .map(|expr| expr.parse().unwrap())
+            .collect::<Vec<Packet>>();
+        assert_eq!(expected, packets);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut reader = PacketReader::hex("D2FX28".as_bytes());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(ParsingError::InvalidHexDigit('X')))
+        ));
+        assert!(reader.next().is_none());
+
+        let mut reader = PacketReader::hex("D2F".as_bytes());
+        assert!(matches!(reader.next(), Some(Err(ParsingError::OOB(12)))));
+        assert!(reader.next().is_none());
+
+        // A tail too short to hold a packet is ignored, like by `decode_all`
+        let packets: Vec<_> = PacketReader::hex("D2FE29".as_bytes()).collect();
+        assert_eq!(1, packets.len());
+        assert_eq!(
+            Packet::new_literal_packet(6, 2021),
+            *packets[0].as_ref().unwrap()
+        );
+
+        assert!(PacketReader::hex("".as_bytes()).next().is_none());
+        assert!(PacketReader::hex("00\n".as_bytes()).next().is_none());
+    }
+}
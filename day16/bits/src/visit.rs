@@ -0,0 +1,284 @@
+//! Traversing `BITS` packet trees without recursion
+//!
+//! All traversals keep their own stack on the heap,
+//! so arbitrarily deep packet trees do not overflow the call stack.
+
+use crate::{Operand, Packet, Payload};
+use std::marker::PhantomData;
+
+/// A visitor that gets called when entering and leaving each packet of a tree
+/// holding literal values of type `L`
+pub trait PacketVisitor<L = u128> {
+    /// Called before the sub packets of `packet` are visited.
+    /// `depth` is `0` for the root packet
+    fn enter(&mut self, _packet: &Packet<L>, _depth: usize) {}
+
+    /// Called after all sub packets of `packet` have been visited
+    fn leave(&mut self, _packet: &Packet<L>, _depth: usize) {}
+}
+
+/// A fold over a packet tree holding literal values of type `L`,
+/// combining the results of the sub packets bottom-up
+pub trait PacketFold<L = u128> {
+    /// The result of folding a packet
+    type Output;
+
+    /// Fold a `literal` packet holding `val`
+    fn literal(&mut self, packet: &Packet<L>, val: L) -> Self::Output;
+
+    /// Fold an `operation` packet from the results of it's sub packets
+    fn op(&mut self, packet: &Packet<L>, op: Operand, childs: Vec<Self::Output>) -> Self::Output;
+}
+
+/// Visit every packet of the tree in depth-first order
+pub fn walk<L, V: PacketVisitor<L> + ?Sized>(packet: &Packet<L>, visitor: &mut V) {
+    visitor.enter(packet, 0);
+    // Each entry holds a packet and the index of it's next sub packet to visit
+    let mut stack = vec![(packet, 0)];
+    while let Some((current, next)) = stack.last_mut() {
+        match current.childs().get(*next) {
+            Some(child) => {
+                *next += 1;
+                visitor.enter(child, stack.len());
+                stack.push((child, 0));
+            }
+            None => {
+                let current = *current;
+                stack.pop();
+                visitor.leave(current, stack.len());
+            }
+        }
+    }
+}
+
+/// Adapts a `PacketFold` into a `PacketVisitor`
+struct FoldVisitor<'a, L, F: PacketFold<L> + ?Sized> {
+    /// The fold to apply
+    folder: &'a mut F,
+
+    /// The results of the packets that have been left, but whose parent has not
+    results: Vec<F::Output>,
+
+    /// The type of the literal values folded
+    literal: PhantomData<L>,
+}
+
+impl<L: Clone, F: PacketFold<L> + ?Sized> PacketVisitor<L> for FoldVisitor<'_, L, F> {
+    fn leave(&mut self, packet: &Packet<L>, _depth: usize) {
+        let ret = match &packet.payload {
+            Payload::Literal(val) => self.folder.literal(packet, val.clone()),
+            Payload::Op(op, childs) => {
+                let childs = self.results.split_off(self.results.len() - childs.len());
+                self.folder.op(packet, *op, childs)
+            }
+        };
+        self.results.push(ret);
+    }
+}
+
+/// Fold the packet tree bottom-up
+pub fn fold<L: Clone, F: PacketFold<L> + ?Sized>(packet: &Packet<L>, folder: &mut F) -> F::Output {
+    let mut visitor = FoldVisitor {
+        folder,
+        results: Vec::new(),
+        literal: PhantomData,
+    };
+    walk(packet, &mut visitor);
+    visitor
+        .results
+        .pop()
+        .expect("folding a tree always yields a result for the root")
+}
+
+/// Sums up the versions of all packets
+#[derive(Debug, Default)]
+struct VersionSum(u128);
+
+impl<L> PacketVisitor<L> for VersionSum {
+    fn enter(&mut self, packet: &Packet<L>, _depth: usize) {
+        self.0 += packet.version as u128;
+    }
+}
+
+/// Calculate the sum of the versions of all packets in the tree
+pub fn version_sum<L>(packet: &Packet<L>) -> u128 {
+    let mut visitor = VersionSum::default();
+    walk(packet, &mut visitor);
+    visitor.0
+}
+
+/// The number of packets in a tree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NodeCount {
+    /// The number of `literal` packets
+    pub literals: usize,
+
+    /// The number of `operation` packets
+    pub operators: usize,
+}
+
+impl NodeCount {
+    /// The number of all packets
+    pub fn total(&self) -> usize {
+        self.literals + self.operators
+    }
+}
+
+impl<L> PacketVisitor<L> for NodeCount {
+    fn enter(&mut self, packet: &Packet<L>, _depth: usize) {
+        match packet.payload {
+            Payload::Literal(_) => self.literals += 1,
+            Payload::Op(..) => self.operators += 1,
+        }
+    }
+}
+
+/// Count the packets in the tree
+pub fn node_count<L>(packet: &Packet<L>) -> NodeCount {
+    let mut visitor = NodeCount::default();
+    walk(packet, &mut visitor);
+    visitor
+}
+
+/// Statistics about the nesting of a tree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DepthStats {
+    /// The depth of the deepest packet, `0` for a single packet
+    pub max_depth: usize,
+
+    /// The number of packets without sub packets
+    pub leaves: usize,
+
+    /// The sum of the depths of all packets without sub packets
+    pub total_leaf_depth: usize,
+}
+
+impl DepthStats {
+    /// The average depth of the packets without sub packets
+    pub fn mean_leaf_depth(&self) -> f64 {
+        if self.leaves == 0 {
+            return 0.0;
+        }
+        self.total_leaf_depth as f64 / self.leaves as f64
+    }
+}
+
+impl<L> PacketVisitor<L> for DepthStats {
+    fn enter(&mut self, packet: &Packet<L>, depth: usize) {
+        self.max_depth = self.max_depth.max(depth);
+        if packet.childs().is_empty() {
+            self.leaves += 1;
+            self.total_leaf_depth += depth;
+        }
+    }
+}
+
+/// Collect statistics about the nesting of the tree
+pub fn depth_stats<L>(packet: &Packet<L>) -> DepthStats {
+    let mut visitor = DepthStats::default();
+    walk(packet, &mut visitor);
+    visitor
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        apply_ops, apply_ops_big, encode_packet, encoded_len, parse_bit_stream, try_apply_ops,
+        EncodingError, LengthTyp,
+    };
+
+    /// Build a chain of `depth` nested `sum` packets around a single literal
+    fn deep_packet(depth: usize) -> Packet {
+        let mut packet = Packet::new_literal_packet(1, 42);
+        for _ in 0..depth {
+            packet = Packet::new_op_packet(1, Operand::Sum, vec![packet]);
+        }
+        packet
+    }
+
+    #[test]
+    fn test_version_sum() {
+        for (s, sum) in [
+            ("8A004A801A8002F478", 16),
+            ("620080001611562C8802118E34", 12),
+            ("C0015000016115A2E0802F182340", 23),
+            ("A0016C880162017C3686B18A3D4780", 31),
+        ] {
+            let packet = parse_bit_stream(s.try_into().unwrap()).unwrap();
+            assert_eq!(sum, version_sum(&packet));
+        }
+    }
+
+    #[test]
+    fn test_statistics() {
+        let packet: Packet = "(sum (lit 1) (max (lit 2) (min (lit 3))) (product))"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            NodeCount {
+                literals: 3,
+                operators: 4
+            },
+            node_count(&packet)
+        );
+        assert_eq!(7, node_count(&packet).total());
+        assert_eq!(
+            DepthStats {
+                max_depth: 3,
+                leaves: 4,
+                total_leaf_depth: 1 + 2 + 3 + 1
+            },
+            depth_stats(&packet)
+        );
+        assert_eq!(1.75, depth_stats(&packet).mean_leaf_depth());
+    }
+
+    #[test]
+    fn test_fold_order() {
+        /// Renders the tree with the sub packets results in order
+        struct Render;
+
+        impl PacketFold for Render {
+            type Output = String;
+
+            fn literal(&mut self, _packet: &Packet, val: u128) -> String {
+                val.to_string()
+            }
+
+            fn op(&mut self, _packet: &Packet, op: Operand, childs: Vec<String>) -> String {
+                format!("{}[{}]", op, childs.join(","))
+            }
+        }
+
+        let packet: Packet = "(sum (lit 1) (max (lit 2) (lit 3)) (lit 4))"
+            .parse()
+            .unwrap();
+        assert_eq!("sum[1,max[2,3],4]", fold(&packet, &mut Render));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 200_000;
+        let packet = deep_packet(depth);
+        assert_eq!(42, apply_ops(&packet));
+        assert_eq!(Ok(42), try_apply_ops(&packet));
+        assert_eq!(Ok(42u8.into()), apply_ops_big(&packet));
+        assert_eq!(depth as u128 + 1, version_sum(&packet));
+        assert_eq!(depth, depth_stats(&packet).max_depth);
+        assert!(packet.to_string().starts_with("(sum@1 (sum@1"));
+
+        assert!(packet == deep_packet(depth));
+        assert!(packet != deep_packet(depth - 1));
+        let bits = encode_packet(&packet, LengthTyp::NumSubPackets).unwrap();
+        assert_eq!(
+            encoded_len(&packet, LengthTyp::NumSubPackets),
+            bits.backing.len()
+        );
+        assert!(bits.to_hex().starts_with("22004880"));
+        assert!(matches!(
+            encode_packet(&packet, LengthTyp::LengthInBits),
+            Err(EncodingError::SubPacketLengthOverflow(_))
+        ));
+    }
+}
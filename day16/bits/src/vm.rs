@@ -0,0 +1,181 @@
+//! Compiling `BITS` packet trees into flat programs for a stack machine
+//!
+//! A program is compiled once and can then be run any number of times
+//! without walking the packet tree again.
+//! The stack needed to run a program is known from compiling it.
+
+use crate::eval::{checked_op, wrapping_op};
+use crate::{walk, EvalError, Operand, OperatorRegistry, Packet, PacketVisitor, Payload};
+use std::convert::Infallible;
+
+/// A single instruction of a compiled program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Push the literal value onto the stack
+    Push(u128),
+
+    /// Pop the given number of values, apply the operation on them
+    /// in the order they were pushed and push the result
+    Apply(Operand, usize),
+}
+
+/// A packet tree compiled into a sequence of instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// The instructions in the order they are executed
+    code: Vec<Instruction>,
+
+    /// The maximum number of values on the stack while running the program
+    max_stack: usize,
+}
+
+/// Emits the instructions of each packet after the ones of it's sub packets
+#[derive(Debug, Default)]
+struct Compiler {
+    /// The instructions emitted so far
+    code: Vec<Instruction>,
+
+    /// The number of values on the stack after the instructions emitted so far
+    stack: usize,
+
+    /// The maximum number of values on the stack so far
+    max_stack: usize,
+}
+
+impl PacketVisitor for Compiler {
+    fn leave(&mut self, packet: &Packet, _depth: usize) {
+        match &packet.payload {
+            Payload::Literal(val) => {
+                self.code.push(Instruction::Push(*val));
+                self.stack += 1;
+            }
+            Payload::Op(op, childs) => {
+                self.code.push(Instruction::Apply(*op, childs.len()));
+                self.stack = self.stack - childs.len() + 1;
+            }
+        }
+        self.max_stack = self.max_stack.max(self.stack);
+    }
+}
+
+impl Program {
+    /// Compile the packet tree into a program
+    pub fn compile(packet: &Packet) -> Self {
+        let mut compiler = Compiler::default();
+        walk(packet, &mut compiler);
+        Self {
+            code: compiler.code,
+            max_stack: compiler.max_stack,
+        }
+    }
+
+    /// The instructions of this program in the order they are executed
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    /// The maximum number of values on the stack while running this program
+    pub fn max_stack(&self) -> usize {
+        self.max_stack
+    }
+
+    /// Run the program, applying each operation with `apply`
+    fn execute<E, F>(&self, mut apply: F) -> Result<u128, E>
+    where
+        F: FnMut(Operand, &[u128]) -> Result<u128, E>,
+    {
+        let mut stack = Vec::with_capacity(self.max_stack);
+        for instruction in &self.code {
+            match *instruction {
+                Instruction::Push(val) => stack.push(val),
+                Instruction::Apply(op, count) => {
+                    let args = stack.len() - count;
+                    let ret = apply(op, &stack[args..])?;
+                    stack.truncate(args);
+                    stack.push(ret);
+                }
+            }
+        }
+        Ok(stack
+            .pop()
+            .expect("a compiled program always leaves the result on the stack"))
+    }
+
+    /// Run the program, wrapping around on overflow like `apply_ops`
+    pub fn run(&self) -> u128 {
+        let Ok(val) = self.execute(|op, vals| Ok::<_, Infallible>(wrapping_op(op, vals)));
+        val
+    }
+
+    /// Run the program, failing if a result does not fit into a `u128` like `try_apply_ops`.
+    /// Custom operations are evaluated with the functions registered in `registry`
+    pub fn try_run(&self, registry: &OperatorRegistry) -> Result<u128, EvalError> {
+        self.execute(|op, vals| checked_op(registry, op, vals))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{random_packets, EXAMPLES};
+    use crate::{apply_ops, parse_bit_stream, try_apply_ops};
+
+    #[test]
+    fn test_compile() {
+        let packet: Packet = "(sum (lit 1) (max (lit 2) (lit 3)) (product))"
+            .parse()
+            .unwrap();
+        let program = Program::compile(&packet);
+        assert_eq!(
+            &[
+                Instruction::Push(1),
+                Instruction::Push(2),
+                Instruction::Push(3),
+                Instruction::Apply(Operand::Maximum, 2),
+                Instruction::Apply(Operand::Product, 0),
+                Instruction::Apply(Operand::Sum, 3),
+            ],
+            program.instructions()
+        );
+        assert_eq!(3, program.max_stack());
+        assert_eq!(5, program.run());
+    }
+
+    #[test]
+    fn test_run_matches_apply_ops() {
+        for (s, expected) in EXAMPLES {
+            let packet = parse_bit_stream(s.try_into().unwrap()).unwrap();
+            let program = Program::compile(&packet);
+            assert_eq!(expected, program.run(), "{}", s);
+            assert_eq!(
+                try_apply_ops(&packet),
+                program.try_run(&OperatorRegistry::new())
+            );
+        }
+
+        for (seed, packet) in random_packets() {
+            let program = Program::compile(&packet);
+            assert_eq!(apply_ops(&packet), program.run(), "seed {}", seed);
+            if let Ok(val) = try_apply_ops(&packet) {
+                assert_eq!(
+                    Ok(val),
+                    program.try_run(&OperatorRegistry::new()),
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let mut packet = Packet::new_literal_packet(0, 7);
+        for _ in 0..200_000 {
+            packet = Packet::new_op_packet(0, Operand::Product, vec![packet]);
+        }
+        let program = Program::compile(&packet);
+        assert_eq!(1, program.max_stack());
+        assert_eq!(7, program.run());
+        assert_eq!(Ok(7), program.try_run(&OperatorRegistry::new()));
+    }
+}